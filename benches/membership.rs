@@ -0,0 +1,49 @@
+//! Compares repeated membership checks on a million-member cluster, scanning
+//! the sequences against the index of `Cluster::contains`.
+//!
+//! Run with `cargo bench --bench membership`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use clstr::Cluster;
+
+const MEMBERS: usize = 1_000_000;
+const QUERIES: usize = 1_000;
+
+fn bench_membership(c: &mut Criterion) {
+    let mut data = String::from(">Cluster 0\n");
+    for i in 0..MEMBERS {
+        data.push_str(&format!("{i}\t100aa, >seq_{i}... at 90.00%\n"));
+    }
+    let cluster: Cluster = data.parse().unwrap();
+
+    // spread the queries over the cluster, with some misses
+    let queries: Vec<String> = (0..QUERIES)
+        .map(|q| format!("seq_{}", q * (MEMBERS / QUERIES) * 11 / 10))
+        .collect();
+    let scan = |cluster: &Cluster| {
+        queries
+            .iter()
+            .filter(|q| cluster.iter().any(|s| s.id() == q.as_str()))
+            .count()
+    };
+    let indexed = |cluster: &Cluster| queries.iter().filter(|q| cluster.contains(q)).count();
+    // checked on a clone, as cloning copies a built index
+    assert_eq!(scan(&cluster), indexed(&cluster.clone()));
+
+    let mut group = c.benchmark_group("membership");
+    group.sample_size(10);
+    group.bench_function("linear_scan", |b| b.iter(|| scan(&cluster)));
+    // a fresh clone each time, so that building the index is included
+    group.bench_function("index_built_on_first_use", |b| {
+        b.iter_batched(
+            || cluster.clone(),
+            |cluster| indexed(&cluster),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_membership);
+criterion_main!(benches);
@@ -0,0 +1,60 @@
+//! Compares parsing a large `.clstr` file on one thread with `from_path`
+//! against `par_from_path` across the rayon thread pool.
+//!
+//! Run with `cargo bench --features parallel`.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rayon::prelude::*;
+
+/// Writes a file of 500,000 clusters of one to five sequences, about 40 MB,
+/// and returns its path.
+fn write_input() -> std::path::PathBuf {
+    let mut data = String::new();
+    for c in 0..500_000 {
+        data.push_str(&format!(">Cluster {c}\n"));
+        for i in 0..(c % 5) {
+            data.push_str(&format!(
+                "{i}\t{}aa, >sp|Q{c:06}|SEQ_{i}... at 9{i}.50%\n",
+                100 + i
+            ));
+        }
+        data.push_str(&format!("{}\t500aa, >sp|P{c:06}|REP... *\n", c % 5));
+    }
+    let path = std::env::temp_dir().join(format!("clstr_bench_{}.clstr", std::process::id()));
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let path = write_input();
+    let len = std::fs::metadata(&path).unwrap().len();
+
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(len));
+    group.sample_size(10);
+    group.bench_function("from_path", |b| {
+        b.iter(|| {
+            clstr::from_path(&path)
+                .unwrap()
+                .filter(Result::is_ok)
+                .count()
+        })
+    });
+    group.bench_function(
+        format!("par_from_path/{}_threads", rayon::current_num_threads()),
+        |b| {
+            b.iter(|| {
+                clstr::par_from_path(&path)
+                    .unwrap()
+                    .filter(Result::is_ok)
+                    .count()
+            })
+        },
+    );
+    group.finish();
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,95 @@
+//! Compares the parser with the line splitting it replaced, which read each
+//! line into a fresh `String` with `lines()` and split it into a `Vec` with
+//! `split_whitespace().collect()`.
+//!
+//! Run with `cargo bench --bench parse`.
+
+use std::io::BufRead;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+/// Returns 100,000 clusters of one to five sequences, about 12 MB.
+fn generate() -> Vec<u8> {
+    let mut data = String::new();
+    for c in 0..100_000 {
+        data.push_str(&format!(">Cluster {c}\n"));
+        for i in 0..(c % 5) {
+            data.push_str(&format!(
+                "{i}\t{}aa, >sp|Q{c:06}|SEQ_{i}... at 9{i}.50%\n",
+                100 + i
+            ));
+        }
+        data.push_str(&format!("{}\t500aa, >sp|P{c:06}|REP... *\n", c % 5));
+    }
+    data.into_bytes()
+}
+
+/// The old parsing of a member line, returning its length, ID, identity and
+/// whether it is the representative.
+fn parse_line_baseline(line: &str) -> Option<(u32, String, Option<f32>, bool)> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() < 3 {
+        return None;
+    }
+    let len_token = parts[1].trim_end_matches(',');
+    let split_pos = len_token
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(len_token.len());
+    let length = len_token[..split_pos].parse().ok()?;
+    let id = parts[2]
+        .trim_start_matches('>')
+        .split("...")
+        .next()?
+        .to_string();
+    let is_representative = line.ends_with('*');
+    let identity = line.find(" at ").and_then(|at| {
+        let token = line[at + 4..].split_whitespace().next()?;
+        let token = token.trim_end_matches('%');
+        let primary = token.split_once('/').map_or(token, |(a, b)| match a {
+            "" | "+" | "-" => b,
+            _ => a,
+        });
+        primary.parse().ok()
+    });
+    Some((length, id, identity, is_representative))
+}
+
+/// Counts the clusters and sequences of `data` the old way.
+fn parse_baseline(data: &[u8]) -> (usize, usize) {
+    let (mut clusters, mut sequences) = (0, 0);
+    for line in data.lines() {
+        let line = line.unwrap();
+        if line.starts_with('>') {
+            clusters += 1;
+        } else if parse_line_baseline(&line).is_some() {
+            sequences += 1;
+        }
+    }
+    (clusters, sequences)
+}
+
+/// Counts the clusters and sequences of `data` with [`clstr::ClstrParser`].
+fn parse_current(data: &[u8]) -> (usize, usize) {
+    let (mut clusters, mut sequences) = (0, 0);
+    for cluster in clstr::ClstrParser::new(data) {
+        clusters += 1;
+        sequences += cluster.unwrap().size();
+    }
+    (clusters, sequences)
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let data = generate();
+    assert_eq!(parse_baseline(&data), parse_current(&data));
+
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("lines_split_whitespace", |b| {
+        b.iter(|| parse_baseline(&data))
+    });
+    group.bench_function("clstr_parser", |b| b.iter(|| parse_current(&data)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,29 @@
+//! Count the sequences in each cluster of a `.clstr` file, reading it
+//! asynchronously. Run with `cargo run --example async_parse --features async
+//! -- <file.clstr>`.
+
+use futures_util::StreamExt;
+use tokio::io::BufReader;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // use the first arg from the cli
+    let clstr_file = std::env::args()
+        .nth(1)
+        .expect("Please provide a .clstr file");
+
+    let file = tokio::fs::File::open(clstr_file).await?;
+    let mut parser = clstr::AsyncClstrParser::new(BufReader::new(file));
+
+    // the parser is a stream of clusters
+    while let Some(cluster) = parser.next().await {
+        let cluster = cluster?;
+        println!(
+            "Cluster {}: {} sequences",
+            cluster.cluster_id(),
+            cluster.size()
+        );
+    }
+
+    Ok(())
+}
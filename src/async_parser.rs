@@ -0,0 +1,198 @@
+/*!
+An asynchronous `.clstr` parser over tokio readers, enabled by the `async`
+(or `tokio`) feature.
+*/
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+use tokio::io::AsyncBufRead;
+
+use crate::{gather_record, Cluster, LineState, ParseOptions, ParseWarning, Result};
+
+/// Asynchronous counterpart to [`crate::ClstrParser`], parsing lines exactly
+/// as the synchronous parser does. Clusters are read either with
+/// [`AsyncClstrParser::next_cluster`] or through the [`Stream`] impl.
+pub struct AsyncClstrParser<R: AsyncBufRead + Unpin> {
+    /// The reader to parse the file.
+    reader: R,
+    /// The current cluster being parsed.
+    current_cluster: Option<Cluster>,
+    /// Buffer holding the line currently being parsed, which may be only
+    /// partly read if reading is pending.
+    buf: Vec<u8>,
+    /// The state of parsing.
+    state: LineState,
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncClstrParser<R> {
+    /// Creates a parser with the default [`ParseOptions`].
+    pub fn new(reader: R) -> Self {
+        AsyncClstrParser::with_options(reader, ParseOptions::default())
+    }
+
+    /// Creates a parser with the given options.
+    pub fn with_options(reader: R, options: ParseOptions) -> Self {
+        AsyncClstrParser {
+            reader,
+            current_cluster: None,
+            buf: Vec::new(),
+            state: LineState::new(options),
+        }
+    }
+
+    /// Returns the 1-based number of the last line read, or 0 if nothing has
+    /// been read yet.
+    pub fn line_number(&self) -> u64 {
+        self.state.line
+    }
+
+    /// Returns the byte offset of the start of the last line read.
+    pub fn byte_offset(&self) -> u64 {
+        self.state.offset
+    }
+
+    /// Returns the warnings raised so far, as
+    /// [`crate::ClstrParser::warnings`] does.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.state.warnings
+    }
+
+    /// Reads the next cluster, or returns `None` at the end of the input.
+    pub async fn next_cluster(&mut self) -> Option<Result<Cluster>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+
+    /// Reads up to and including the next newline into `buf`, keeping what
+    /// was read if the reader is pending. Returns the length of the line,
+    /// which is 0 at the end of the input.
+    fn poll_read_line(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        loop {
+            let available = ready!(Pin::new(&mut self.reader).poll_fill_buf(cx))?;
+            if available.is_empty() {
+                return Poll::Ready(Ok(self.buf.len()));
+            }
+
+            let (used, done) = match available.iter().position(|&b| b == b'\n') {
+                Some(i) => (i + 1, true),
+                None => (available.len(), false),
+            };
+            self.buf.extend_from_slice(&available[..used]);
+            Pin::new(&mut self.reader).consume(used);
+
+            if done {
+                return Poll::Ready(Ok(self.buf.len()));
+            }
+        }
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for AsyncClstrParser<R> {
+    type Item = Result<Cluster>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let read = ready!(this.poll_read_line(cx));
+            let parsed = match read {
+                Ok(0) => return Poll::Ready(this.current_cluster.take().map(Ok)),
+                Ok(_) => this.state.parse_line(&this.buf),
+                Err(e) => Some(Err(this.state.read_error(e))),
+            };
+            this.buf.clear();
+
+            match parsed {
+                Some(Ok(record)) => {
+                    if let Some(c) = gather_record(&mut this.current_cluster, record) {
+                        return Poll::Ready(Some(Ok(c)));
+                    }
+                }
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[tokio::test]
+    async fn test_async_parser() {
+        let data = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+>Cluster 4
+0    122nt, >::SUPER_5:20757114-20757236... at -/97.54%
+1    198nt, >::SUPER_2:18124787-18124985... *
+";
+
+        let reader = tokio::io::BufReader::new(Cursor::new(data.as_bytes()));
+        let mut parser = AsyncClstrParser::new(reader);
+
+        let mut clusters = Vec::new();
+        while let Some(cluster) = parser.next_cluster().await {
+            clusters.push(cluster.unwrap());
+        }
+
+        let sync: Vec<Cluster> = crate::from_str(data).collect::<Result<_>>().unwrap();
+        assert_eq!(clusters.len(), sync.len());
+        for (a, b) in clusters.iter().zip(sync.iter()) {
+            assert_eq!(a.cluster_id(), b.cluster_id());
+            assert_eq!(a.size(), b.size());
+            for (x, y) in a.sequences().iter().zip(b.sequences().iter()) {
+                assert_eq!(x.id(), y.id());
+                assert_eq!(x.identity(), y.identity());
+                assert_eq!(x.is_representative(), y.is_representative());
+            }
+        }
+        assert_eq!(parser.line_number(), 6);
+
+        let reader =
+            tokio::io::BufReader::new(Cursor::new(&b">Cluster 0\n0    1bp, >x... *\n"[..]));
+        let mut parser = AsyncClstrParser::new(reader);
+        assert!(parser.next_cluster().await.unwrap().is_err());
+
+        // a cut header drops only itself, and a cut member line its cluster
+        let options = || ParseOptions {
+            on_truncation: crate::TruncationPolicy::Drop,
+            ..Default::default()
+        };
+        for (data, sizes) in [
+            (&b">Cluster 0\n0    100aa, >a... *\n>Cluster 1"[..], vec![1]),
+            (
+                &b">Cluster 0\n0    100aa, >a... *\n>Cluster 1\n0    9"[..],
+                vec![1],
+            ),
+            (&b">Cluster 0\n0    100aa, >a... *\n1    9"[..], vec![]),
+        ] {
+            let mut parser = AsyncClstrParser::with_options(Cursor::new(data), options());
+            let mut got = Vec::new();
+            while let Some(cluster) = parser.next_cluster().await {
+                got.push(cluster.unwrap().size());
+            }
+            assert_eq!(got, sizes);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_stream() {
+        use futures_util::StreamExt;
+
+        let data =
+            std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_nt.clstr")).unwrap();
+        // a tiny buffer splits lines across reads
+        let reader = tokio::io::BufReader::with_capacity(7, Cursor::new(data.clone()));
+        let clusters: Vec<Cluster> = AsyncClstrParser::new(reader)
+            .map(|c| c.unwrap())
+            .collect()
+            .await;
+
+        let sync: Vec<Cluster> = crate::from_str(&data).collect::<Result<_>>().unwrap();
+        assert_eq!(clusters, sync);
+    }
+}
@@ -0,0 +1,222 @@
+/*!
+Agreement between two clusterings of the same sequences, as when one set is
+clustered at two identity thresholds.
+*/
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::ClusterSet;
+
+/// How the pairs of shared sequences fall in two clusterings, returned as
+/// part of a [`ComparisonReport`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PairCounts {
+    /// Pairs in the same cluster in both.
+    pub together_in_both: u128,
+    /// Pairs in the same cluster in the first only.
+    pub together_in_a: u128,
+    /// Pairs in the same cluster in the second only.
+    pub together_in_b: u128,
+    /// Pairs in different clusters in both.
+    pub apart_in_both: u128,
+}
+
+/// The agreement between two clusterings, returned by [`compare`]. Only the
+/// sequence IDs in both are compared, and clusters are told apart by
+/// cluster ID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparisonReport {
+    /// The number of sequence IDs in both clusterings.
+    pub shared_count: usize,
+    /// The number of sequence IDs in the first clustering only.
+    pub only_in_a: usize,
+    /// The number of sequence IDs in the second clustering only.
+    pub only_in_b: usize,
+    /// The number of shared sequences in each pair of a cluster ID in the
+    /// first and one in the second, for the pairs with any.
+    pub contingency: BTreeMap<(usize, usize), u64>,
+    /// How the pairs of shared sequences are split.
+    pub pairs: PairCounts,
+    /// The adjusted Rand index, 1 for identical clusterings and around 0 for
+    /// chance agreement, or `None` with fewer than two shared sequences.
+    pub adjusted_rand_index: Option<f64>,
+    /// The mutual information over the mean of the two entropies, from 0 to
+    /// 1, or `None` with no shared sequences.
+    pub normalized_mutual_info: Option<f64>,
+}
+
+/// Returns the number of unordered pairs among `n`.
+fn pairs(n: u64) -> u128 {
+    let n = u128::from(n);
+    n * n.saturating_sub(1) / 2
+}
+
+/// Returns the entropy, in nats, of clusters of the given sizes out of `n`.
+fn entropy<'a, I: IntoIterator<Item = &'a u64>>(sizes: I, n: f64) -> f64 {
+    sizes
+        .into_iter()
+        .map(|&size| {
+            let p = size as f64 / n;
+            -p * p.ln()
+        })
+        .sum()
+}
+
+/// Compares the clusterings `a` and `b` over the sequence IDs in both. A
+/// sequence in more than one cluster of a set is counted in the first, as
+/// [`ClusterSet::cluster_of`] finds it.
+pub fn compare(a: &ClusterSet, b: &ClusterSet) -> ComparisonReport {
+    let mut contingency: BTreeMap<(usize, usize), u64> = BTreeMap::new();
+    let (mut only_in_a, mut only_in_b) = (0, 0);
+
+    let mut seen = HashSet::new();
+    for seq in a.iter().flat_map(|c| c.iter()) {
+        if !seen.insert(seq.id()) {
+            continue;
+        }
+        match (a.cluster_of(seq.id()), b.cluster_of(seq.id())) {
+            (Some(in_a), Some(in_b)) => *contingency.entry((in_a, in_b)).or_insert(0) += 1,
+            _ => only_in_a += 1,
+        }
+    }
+    seen.clear();
+    for seq in b.iter().flat_map(|c| c.iter()) {
+        if seen.insert(seq.id()) && a.cluster_of(seq.id()).is_none() {
+            only_in_b += 1;
+        }
+    }
+
+    let mut a_sizes: BTreeMap<usize, u64> = BTreeMap::new();
+    let mut b_sizes: BTreeMap<usize, u64> = BTreeMap::new();
+    for (&(in_a, in_b), &count) in &contingency {
+        *a_sizes.entry(in_a).or_insert(0) += count;
+        *b_sizes.entry(in_b).or_insert(0) += count;
+    }
+    let shared: u64 = contingency.values().sum();
+
+    let together_in_both: u128 = contingency.values().map(|&n| pairs(n)).sum();
+    let together_a: u128 = a_sizes.values().map(|&n| pairs(n)).sum();
+    let together_b: u128 = b_sizes.values().map(|&n| pairs(n)).sum();
+    let all_pairs = pairs(shared);
+    let counts = PairCounts {
+        together_in_both,
+        together_in_a: together_a - together_in_both,
+        together_in_b: together_b - together_in_both,
+        apart_in_both: all_pairs + together_in_both - together_a - together_b,
+    };
+
+    let adjusted_rand_index = (all_pairs > 0).then(|| {
+        let expected = together_a as f64 * together_b as f64 / all_pairs as f64;
+        let max = (together_a as f64 + together_b as f64) / 2.0;
+        // only identical clusterings, all singletons or all one cluster,
+        // leave nothing between the expected and the maximum
+        if max == expected {
+            1.0
+        } else {
+            (together_in_both as f64 - expected) / (max - expected)
+        }
+    });
+
+    let normalized_mutual_info = (shared > 0).then(|| {
+        let n = shared as f64;
+        let mutual_info: f64 = contingency
+            .iter()
+            .map(|((in_a, in_b), &count)| {
+                let joint = count as f64 / n;
+                let (p_a, p_b) = (a_sizes[in_a] as f64 / n, b_sizes[in_b] as f64 / n);
+                joint * (joint / (p_a * p_b)).ln()
+            })
+            .sum();
+        let mean_entropy = (entropy(a_sizes.values(), n) + entropy(b_sizes.values(), n)) / 2.0;
+        // both clusterings are a single cluster
+        if mean_entropy == 0.0 {
+            1.0
+        } else {
+            (mutual_info / mean_entropy).clamp(0.0, 1.0)
+        }
+    });
+
+    ComparisonReport {
+        shared_count: shared as usize,
+        only_in_a,
+        only_in_b,
+        contingency,
+        pairs: counts,
+        adjusted_rand_index,
+        normalized_mutual_info,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Result;
+
+    fn set(data: &str) -> ClusterSet {
+        crate::from_str(data).collect::<Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn test_compare() {
+        let a = set(">Cluster 0
+0    100aa, >s1... *
+1    100aa, >s2... at 90.00%
+2    100aa, >s3... at 90.00%
+>Cluster 1
+0    100aa, >s4... *
+1    100aa, >s5... at 90.00%
+2    100aa, >s6... at 90.00%
+3    100aa, >s7... at 90.00%
+");
+        let b = set(">Cluster 0
+0    100aa, >s1... *
+1    100aa, >s2... at 90.00%
+>Cluster 1
+0    100aa, >s3... *
+1    100aa, >s4... at 90.00%
+>Cluster 2
+0    100aa, >s5... *
+1    100aa, >s6... at 90.00%
+2    100aa, >s8... at 90.00%
+>Cluster 3
+0    100aa, >s9... *
+");
+        let report = compare(&a, &b);
+        assert_eq!(report.shared_count, 6);
+        assert_eq!(report.only_in_a, 1);
+        assert_eq!(report.only_in_b, 2);
+        assert_eq!(
+            report.contingency,
+            BTreeMap::from([((0, 0), 2), ((0, 1), 1), ((1, 1), 1), ((1, 2), 2)])
+        );
+        assert_eq!(
+            report.pairs,
+            PairCounts {
+                together_in_both: 2,
+                together_in_a: 4,
+                together_in_b: 1,
+                apart_in_both: 8,
+            }
+        );
+
+        // of 15 pairs, 6 are together in a and 3 in b, so 1.2 are expected
+        // together in both by chance, against a maximum of 4.5
+        let ari = report.adjusted_rand_index.unwrap();
+        assert!((ari - (2.0 - 1.2) / (4.5 - 1.2)).abs() < 1e-12);
+        // the mutual information is 2/3 ln 2, against entropies of ln 2 and
+        // ln 3
+        let nmi = report.normalized_mutual_info.unwrap();
+        let expected = (2.0 / 3.0 * 2f64.ln()) / ((2f64.ln() + 3f64.ln()) / 2.0);
+        assert!((nmi - expected).abs() < 1e-12);
+
+        let same = compare(&a, &a);
+        assert_eq!(same.adjusted_rand_index, Some(1.0));
+        assert!((same.normalized_mutual_info.unwrap() - 1.0).abs() < 1e-12);
+        assert_eq!(same.only_in_a + same.only_in_b, 0);
+
+        let disjoint = compare(&a, &set(">Cluster 0\n0    100aa, >x... *\n"));
+        assert_eq!(disjoint.shared_count, 0);
+        assert_eq!(disjoint.adjusted_rand_index, None);
+        assert_eq!(disjoint.normalized_mutual_info, None);
+    }
+}
@@ -0,0 +1,496 @@
+/*!
+A whole `.clstr` file held in memory, for when clusters need random access.
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+use std::ops::{Deref, DerefMut, Index};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{ClstrParser, Cluster, Error, Result};
+
+/// All of the clusters of a `.clstr` file, in file order.
+#[derive(Debug, Default)]
+pub struct ClstrFile {
+    /// The clusters.
+    clusters: Vec<Cluster>,
+}
+
+impl ClstrFile {
+    /// Reads every cluster from `parser`, reserving room for as many as
+    /// [`ClstrParser::estimated_clusters`] guesses.
+    pub fn from_parser<R: BufRead>(parser: ClstrParser<R>) -> Result<ClstrFile> {
+        let mut clusters = Vec::with_capacity(parser.estimated_clusters().unwrap_or(0));
+        for cluster in parser {
+            clusters.push(cluster?);
+        }
+        Ok(ClstrFile { clusters })
+    }
+
+    /// Reads every cluster from the file at `path`, as [`crate::from_path`]
+    /// would.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<ClstrFile> {
+        ClstrFile::from_parser(crate::from_path(path)?)
+    }
+
+    /// Returns the clusters.
+    pub fn clusters(&self) -> &[Cluster] {
+        &self.clusters
+    }
+
+    /// Returns the clusters, consuming the file.
+    pub fn into_clusters(self) -> Vec<Cluster> {
+        self.clusters
+    }
+
+    /// Appends the clusters of `other` to this file, renumbering them to
+    /// continue on from the highest cluster ID here. A cluster of this file
+    /// that repeats an earlier cluster ID is renumbered the same way, so
+    /// every ID in the result is distinct.
+    pub fn merge(mut self, other: ClstrFile) -> ClstrFile {
+        let next_id = self.renumber_repeated_ids();
+
+        self.clusters
+            .extend(other.clusters.into_iter().enumerate().map(|(i, mut c)| {
+                c.cluster_id = next_id + i;
+                c
+            }));
+        self
+    }
+
+    /// Gives each cluster that repeats an earlier cluster ID the next ID
+    /// after the highest, and returns the ID after that.
+    fn renumber_repeated_ids(&mut self) -> usize {
+        let mut next_id = self
+            .clusters
+            .iter()
+            .map(Cluster::cluster_id)
+            .max()
+            .map_or(0, |id| id + 1);
+
+        let mut seen = HashSet::with_capacity(self.clusters.len());
+        for cluster in &mut self.clusters {
+            if !seen.insert(cluster.cluster_id) {
+                cluster.cluster_id = next_id;
+                next_id += 1;
+            }
+        }
+        next_id
+    }
+
+    /// Returns the first cluster with the given ID. This scans every cluster;
+    /// [`ClstrFile::build_index`] makes repeated lookups constant time.
+    pub fn cluster_by_id(&self, cluster_id: usize) -> Option<&Cluster> {
+        self.clusters.iter().find(|c| c.cluster_id() == cluster_id)
+    }
+
+    /// Indexes the clusters by cluster ID, consuming the file.
+    pub fn build_index(self) -> IndexedClstrFile {
+        let mut by_id = HashMap::with_capacity(self.clusters.len());
+        for (position, cluster) in self.clusters.iter().enumerate() {
+            by_id.entry(cluster.cluster_id()).or_insert(position);
+        }
+        IndexedClstrFile { file: self, by_id }
+    }
+
+    /// Returns a copy of this file with each sequence ID that is a key of
+    /// `mapping` replaced by its value. IDs not in `mapping` are left as
+    /// they are.
+    pub fn rename_sequences(&self, mapping: &HashMap<String, String>) -> ClstrFile {
+        let mut renamed = ClstrFile {
+            clusters: self.clusters.clone(),
+        };
+        for seq in renamed.clusters.iter_mut().flat_map(Cluster::iter_mut) {
+            if let Some(new_id) = mapping.get(seq.id()) {
+                seq.set_id(new_id.clone());
+            }
+        }
+        renamed
+    }
+
+    /// Returns the number of clusters with exactly one sequence.
+    pub fn singleton_count(&self) -> usize {
+        self.clusters.iter().filter(|c| c.is_singleton()).count()
+    }
+
+    /// Returns the number of clusters with other than one sequence.
+    pub fn non_singleton_count(&self) -> usize {
+        self.clusters.len() - self.singleton_count()
+    }
+
+    /// Returns the largest clusters, largest first, up to and including the
+    /// one that brings their total number of sequences to at least `n`. All
+    /// clusters are returned if there are fewer than `n` sequences. Clusters
+    /// of equal size are in order of cluster ID and then of file.
+    pub fn top_by_cumulative_sequences(&self, n: usize) -> Vec<&Cluster> {
+        let mut by_size: Vec<&Cluster> = self.clusters.iter().collect();
+        by_size.sort_by_key(|c| (std::cmp::Reverse(c.size()), c.cluster_id()));
+
+        let mut total = 0;
+        by_size
+            .into_iter()
+            .take_while(|c| {
+                let more = total < n;
+                total += c.size();
+                more
+            })
+            .collect()
+    }
+
+    /// Returns the position and cluster of the first cluster containing a
+    /// sequence with the given ID. This scans every cluster, without building
+    /// the ID index of [`Cluster::contains`] in each one.
+    pub fn find_cluster_containing(&self, id: &str) -> Option<(usize, &Cluster)> {
+        self.clusters
+            .iter()
+            .enumerate()
+            .find(|(_, c)| c.iter().any(|s| s.id() == id))
+    }
+
+    /// Returns a parallel iterator over the clusters, for expensive work on
+    /// each one. Reading them is sequential, so
+    /// [`ClstrFile::from_path_parallel`] is the faster way to parse a large
+    /// file.
+    #[cfg(feature = "parallel")]
+    pub fn par_iter(&self) -> rayon::slice::Iter<'_, Cluster> {
+        use rayon::prelude::*;
+        self.clusters.par_iter()
+    }
+
+    /// Reads every cluster from the file at `path` across the rayon thread
+    /// pool with [`crate::par_from_path`], keeping them in file order. A
+    /// parser reads its input in order, so there is no parallel
+    /// [`ClstrFile::from_parser`].
+    #[cfg(feature = "parallel")]
+    pub fn from_path_parallel<P: AsRef<Path>>(path: P) -> Result<ClstrFile> {
+        use rayon::prelude::*;
+        Ok(ClstrFile {
+            clusters: crate::par_from_path(path)?.collect::<Result<_>>()?,
+        })
+    }
+
+    /// Returns the clusters containing at least one sequence with an ID in
+    /// `ids`, in file order.
+    pub fn filter_by_ids(&self, ids: &HashSet<&str>) -> Vec<&Cluster> {
+        self.clusters
+            .iter()
+            .filter(|c| c.sequences().iter().any(|s| ids.contains(s.id())))
+            .collect()
+    }
+}
+
+/// A [`ClstrFile`] with a hash index by cluster ID, returned by
+/// [`ClstrFile::build_index`]. It derefs to the file, which cannot be
+/// modified without [`IndexedClstrFile::into_inner`] so that the index stays
+/// correct.
+#[derive(Debug, Default)]
+pub struct IndexedClstrFile {
+    /// The indexed file.
+    file: ClstrFile,
+    /// Maps each cluster ID to the position of its first cluster.
+    by_id: HashMap<usize, usize>,
+}
+
+impl IndexedClstrFile {
+    /// Returns the first cluster with the given ID, in constant time.
+    pub fn cluster_by_id(&self, cluster_id: usize) -> Option<&Cluster> {
+        self.by_id
+            .get(&cluster_id)
+            .map(|&position| &self.file.clusters[position])
+    }
+
+    /// Returns the file, dropping the index.
+    pub fn into_inner(self) -> ClstrFile {
+        self.file
+    }
+}
+
+impl Deref for IndexedClstrFile {
+    type Target = ClstrFile;
+
+    fn deref(&self) -> &ClstrFile {
+        &self.file
+    }
+}
+
+/// Merges several files into one with [`ClstrFile::merge`], in order. A
+/// single file is still renumbered so that its cluster IDs are distinct.
+pub fn merge_all<I: IntoIterator<Item = ClstrFile>>(files: I) -> ClstrFile {
+    let mut merged = files
+        .into_iter()
+        .reduce(ClstrFile::merge)
+        .unwrap_or_default();
+    merged.renumber_repeated_ids();
+    merged
+}
+
+impl From<Vec<Cluster>> for ClstrFile {
+    fn from(clusters: Vec<Cluster>) -> Self {
+        ClstrFile { clusters }
+    }
+}
+
+impl FromStr for ClstrFile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ClstrFile::from_parser(crate::from_str(s))
+    }
+}
+
+impl Deref for ClstrFile {
+    type Target = [Cluster];
+
+    fn deref(&self) -> &[Cluster] {
+        &self.clusters
+    }
+}
+
+impl DerefMut for ClstrFile {
+    fn deref_mut(&mut self) -> &mut [Cluster] {
+        &mut self.clusters
+    }
+}
+
+impl Index<usize> for ClstrFile {
+    type Output = Cluster;
+
+    fn index(&self, index: usize) -> &Cluster {
+        &self.clusters[index]
+    }
+}
+
+impl IntoIterator for ClstrFile {
+    type Item = Cluster;
+    type IntoIter = std::vec::IntoIter<Cluster>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.clusters.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ClstrFile {
+    type Item = &'a Cluster;
+    type IntoIter = std::slice::Iter<'a, Cluster>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.clusters.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clstr_file() {
+        let file = ClstrFile::from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_nt.clstr"))
+            .unwrap();
+
+        assert_eq!(file.len(), 4);
+        assert_eq!(file[1].cluster_id(), 477);
+        assert_eq!(file.iter().map(Cluster::size).max(), Some(6));
+        assert_eq!(file.singleton_count(), 3);
+        assert_eq!(file.non_singleton_count(), 1);
+
+        let (position, cluster) = file
+            .find_cluster_containing("::SUPER_2:8753913-8754035")
+            .unwrap();
+        assert_eq!(position, 3);
+        assert_eq!(cluster.cluster_id(), 479);
+        assert!(file.find_cluster_containing("::SUPER_9:1-2").is_none());
+
+        let ids = HashSet::from(["::SUPER_2:8156692-8156892", "::SUPER_4:13803815-13804001"]);
+        let found: Vec<usize> = file
+            .filter_by_ids(&ids)
+            .iter()
+            .map(|c| c.cluster_id())
+            .collect();
+        assert_eq!(found, vec![477, 479]);
+
+        let ids: Vec<usize> = file.into_iter().map(|c| c.cluster_id()).collect();
+        assert_eq!(ids, vec![476, 477, 478, 479]);
+
+        let parsed: ClstrFile = ">Cluster 2\n0    4481aa, >sp|P0C6T5|R1A_BCHK5... *\n"
+            .parse()
+            .unwrap();
+        assert_eq!(parsed[0].cluster_id(), 2);
+        assert!(">Cluster x\n".parse::<ClstrFile>().is_err());
+    }
+
+    #[test]
+    fn test_cluster_by_id() {
+        let file: ClstrFile = ">Cluster 5
+0    100aa, >a... *
+>Cluster 2
+0    100aa, >b... *
+>Cluster 5
+0    100aa, >c... *
+"
+        .parse()
+        .unwrap();
+        assert_eq!(file.cluster_by_id(2).unwrap().sequences()[0].id(), "b");
+        assert_eq!(file.cluster_by_id(5).unwrap().sequences()[0].id(), "a");
+        assert!(file.cluster_by_id(0).is_none());
+
+        let indexed = file.build_index();
+        assert_eq!(indexed.cluster_by_id(2).unwrap().sequences()[0].id(), "b");
+        assert_eq!(indexed.cluster_by_id(5).unwrap().sequences()[0].id(), "a");
+        assert!(indexed.cluster_by_id(0).is_none());
+        // the file's own methods are still there
+        assert_eq!(indexed.singleton_count(), 3);
+        assert_eq!(indexed.into_inner().len(), 3);
+    }
+
+    #[test]
+    fn test_rename_sequences() {
+        let file: ClstrFile = ">Cluster 0
+0    100aa, >a... *
+1    100aa, >b... at 90.00%
+>Cluster 1
+0    100aa, >c... *
+"
+        .parse()
+        .unwrap();
+        let mapping = HashMap::from([
+            ("a".to_string(), "x".to_string()),
+            ("c".to_string(), "z".to_string()),
+            ("q".to_string(), "unused".to_string()),
+        ]);
+
+        let renamed = file.rename_sequences(&mapping);
+        let ids: Vec<&str> = renamed
+            .iter()
+            .flat_map(|c| c.iter().map(|s| s.id()))
+            .collect();
+        assert_eq!(ids, vec!["x", "b", "z"]);
+        assert!(renamed[0].contains_id("x"));
+        assert!(!renamed[0].contains_id("a"));
+        assert_eq!(renamed[0].get_representative().unwrap().id(), "x");
+        // the original is untouched
+        assert_eq!(file[0].sequences()[0].id(), "a");
+    }
+
+    #[test]
+    fn test_merge() {
+        let a: ClstrFile = ">Cluster 0
+0    4481aa, >a1... *
+>Cluster 3
+0    4471aa, >a2... *
+"
+        .parse()
+        .unwrap();
+        let b: ClstrFile = ">Cluster 0
+0    4441aa, >b1... *
+>Cluster 0
+0    4421aa, >b2... *
+"
+        .parse()
+        .unwrap();
+        let c: ClstrFile = ">Cluster 9\n0    100aa, >c1... *\n".parse().unwrap();
+
+        let merged = merge_all([a, b, c]);
+        let ids: Vec<usize> = merged.iter().map(Cluster::cluster_id).collect();
+        assert_eq!(ids, vec![0, 3, 4, 5, 6]);
+        assert_eq!(merged[4].sequences()[0].id(), "c1");
+
+        assert!(merge_all(Vec::new()).is_empty());
+
+        // a repeated ID in the first file is renumbered too
+        let repeated = ">Cluster 0
+0    4481aa, >r1... *
+>Cluster 0
+0    4471aa, >r2... *
+>Cluster 1
+0    4461aa, >r3... *
+";
+        let c: ClstrFile = ">Cluster 0\n0    100aa, >c1... *\n".parse().unwrap();
+        let ids: Vec<usize> = repeated
+            .parse::<ClstrFile>()
+            .unwrap()
+            .merge(c)
+            .iter()
+            .map(Cluster::cluster_id)
+            .collect();
+        assert_eq!(ids, vec![0, 2, 1, 3]);
+        let ids: Vec<usize> = merge_all([repeated.parse().unwrap()])
+            .iter()
+            .map(Cluster::cluster_id)
+            .collect();
+        assert_eq!(ids, vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn test_top_by_cumulative_sequences() {
+        let file: ClstrFile = ">Cluster 0
+0    100aa, >a... *
+>Cluster 1
+0    100aa, >b... *
+1    100aa, >c... at 90.00%
+>Cluster 2
+0    100aa, >d... *
+1    100aa, >e... at 90.00%
+2    100aa, >f... at 90.00%
+>Cluster 3
+0    100aa, >g... *
+1    100aa, >h... at 90.00%
+"
+        .parse()
+        .unwrap();
+        let ids = |n| -> Vec<usize> {
+            file.top_by_cumulative_sequences(n)
+                .iter()
+                .map(|c| c.cluster_id())
+                .collect()
+        };
+
+        assert_eq!(ids(0), Vec::<usize>::new());
+        assert_eq!(ids(3), vec![2]);
+        // the cluster reaching the total is included
+        assert_eq!(ids(4), vec![2, 1]);
+        assert_eq!(ids(5), vec![2, 1]);
+        assert_eq!(ids(100), vec![2, 1, 3, 0]);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_par_iter() {
+        use rayon::prelude::*;
+
+        let file = ClstrFile::from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_nt.clstr"))
+            .unwrap();
+        let sizes: Vec<usize> = file.par_iter().map(Cluster::size).collect();
+        assert_eq!(sizes, vec![1, 1, 1, 6]);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_from_path_parallel() {
+        // large enough to be split into chunks, and parsed by several threads
+        // even on a single core
+        let mut data = String::new();
+        for c in 0..40_000 {
+            data.push_str(&format!(">Cluster {c}\n0\t500aa, >seq_{c}... *\n"));
+            for i in 1..=(c % 4) {
+                data.push_str(&format!("{i}\t{}aa, >seq_{c}_{i}... at 90.00%\n", 100 + i));
+            }
+        }
+        let path = crate::tests::temp_path("from_path_parallel.clstr");
+        std::fs::write(&path, &data).unwrap();
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let parallel = pool
+            .install(|| ClstrFile::from_path_parallel(&path))
+            .unwrap();
+        let sequential = ClstrFile::from_path(&path).unwrap();
+        assert_eq!(parallel.len(), 40_000);
+        assert!(parallel.iter().eq(sequential.iter()));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(ClstrFile::from_path_parallel(&path).is_err());
+    }
+}
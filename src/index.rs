@@ -0,0 +1,332 @@
+/*!
+A byte-offset index over the clusters of an uncompressed `.clstr` file, for
+reading single clusters without parsing everything before them.
+*/
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::RangeBounds;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::{parse_cluster_header, ClstrParser, ClstrReader, Cluster, Error, ErrorKind, Result};
+
+/// The magic bytes at the start of an index file.
+const INDEX_MAGIC: &[u8; 4] = b"CIDX";
+/// The version of the index file layout.
+const INDEX_VERSION: u32 = 1;
+
+/// The location of a single cluster in a `.clstr` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// The cluster ID from the header.
+    pub cluster_id: usize,
+    /// The byte offset of the cluster header.
+    pub offset: u64,
+    /// The length in bytes of the cluster, header included.
+    pub len: u64,
+}
+
+/// The byte offsets of every cluster in a `.clstr` file, along with the
+/// length and modification time of the file it was built from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClstrIndex {
+    /// The length of the indexed file.
+    file_len: u64,
+    /// The modification time of the indexed file, as seconds and nanoseconds
+    /// since the Unix epoch, or zero if unavailable.
+    modified: (u64, u32),
+    /// The clusters, in file order.
+    entries: Vec<IndexEntry>,
+}
+
+/// Returns the length and modification time of a file, as stored in an index.
+fn file_stamp(file: &File) -> Result<(u64, (u64, u32))> {
+    let metadata = file.metadata()?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or((0, 0), |d| (d.as_secs(), d.subsec_nanos()));
+    Ok((metadata.len(), modified))
+}
+
+/// Build a [`ClstrIndex`] for the uncompressed `.clstr` file at `path` in a
+/// single pass. Only header lines are parsed.
+pub fn build_index<P: AsRef<Path>>(path: P) -> Result<ClstrIndex> {
+    let mut reader = match ClstrReader::from_path(path)? {
+        ClstrReader::Plain(reader) => reader,
+        _ => {
+            return Err(Error::new(ErrorKind::ReadRecord(
+                "can only index an uncompressed file".to_string(),
+            )))
+        }
+    };
+    let (file_len, modified) = file_stamp(reader.get_ref())?;
+
+    let mut entries: Vec<IndexEntry> = Vec::new();
+    let mut line = Vec::new();
+    let mut offset = 0;
+    loop {
+        line.clear();
+        let n = reader.read_until(b'\n', &mut line)? as u64;
+        if n == 0 {
+            break;
+        }
+
+        if line.starts_with(b">") {
+            let header = String::from_utf8_lossy(&line);
+            let cluster_id = parse_cluster_header(header.trim_ascii_end())?;
+            if let Some(last) = entries.last_mut() {
+                last.len = offset - last.offset;
+            }
+            entries.push(IndexEntry {
+                cluster_id,
+                offset,
+                len: 0,
+            });
+        }
+        offset += n;
+    }
+    if let Some(last) = entries.last_mut() {
+        last.len = offset - last.offset;
+    }
+
+    Ok(ClstrIndex {
+        file_len,
+        modified,
+        entries,
+    })
+}
+
+/// Returns the path of the sidecar index file for a `.clstr` file, which is
+/// the same path with `.cidx` appended.
+pub fn index_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut index = path.as_ref().as_os_str().to_owned();
+    index.push(".cidx");
+    PathBuf::from(index)
+}
+
+/// Read a little-endian `u64` from `reader`.
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Read a little-endian `u32` from `reader`.
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+impl ClstrIndex {
+    /// Returns the indexed clusters, in file order.
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Returns whether this index still describes the file at `path`, judged
+    /// by its length and modification time.
+    pub fn is_valid_for<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let (file_len, modified) = file_stamp(&File::open(path)?)?;
+        Ok(file_len == self.file_len && modified == self.modified)
+    }
+
+    /// Writes this index to `path` in a small binary format.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = std::io::BufWriter::new(File::create(path)?);
+        writer.write_all(INDEX_MAGIC)?;
+        writer.write_all(&INDEX_VERSION.to_le_bytes())?;
+        writer.write_all(&self.file_len.to_le_bytes())?;
+        writer.write_all(&self.modified.0.to_le_bytes())?;
+        writer.write_all(&self.modified.1.to_le_bytes())?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for entry in &self.entries {
+            writer.write_all(&(entry.cluster_id as u64).to_le_bytes())?;
+            writer.write_all(&entry.offset.to_le_bytes())?;
+            writer.write_all(&entry.len.to_le_bytes())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads an index written by [`ClstrIndex::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ClstrIndex> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let version = read_u32(&mut reader)?;
+        if &magic != INDEX_MAGIC || version != INDEX_VERSION {
+            return Err(Error::new(ErrorKind::ReadRecord(
+                "not a clstr index file, or an unsupported version".to_string(),
+            )));
+        }
+
+        let file_len = read_u64(&mut reader)?;
+        let modified = (read_u64(&mut reader)?, read_u32(&mut reader)?);
+        let count = read_u64(&mut reader)?;
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            entries.push(IndexEntry {
+                cluster_id: read_u64(&mut reader)? as usize,
+                offset: read_u64(&mut reader)?,
+                len: read_u64(&mut reader)?,
+            });
+        }
+
+        Ok(ClstrIndex {
+            file_len,
+            modified,
+            entries,
+        })
+    }
+}
+
+/// Random access to the clusters of a `.clstr` file through a [`ClstrIndex`].
+pub struct IndexedReader {
+    /// The indexed file.
+    file: File,
+    /// The index over `file`.
+    index: ClstrIndex,
+    /// Maps each cluster ID to its first entry in the index.
+    by_id: HashMap<usize, usize>,
+}
+
+impl IndexedReader {
+    /// Opens the file at `path`, using its sidecar index if that is present
+    /// and up to date, and building a fresh index otherwise.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<IndexedReader> {
+        let path = path.as_ref();
+        let sidecar = index_path(path);
+
+        let index = match ClstrIndex::load(&sidecar) {
+            Ok(index) if index.is_valid_for(path)? => index,
+            _ => build_index(path)?,
+        };
+
+        IndexedReader::with_index(path, index)
+    }
+
+    /// Opens the file at `path` with the given index, failing if the index
+    /// is out of date.
+    pub fn with_index<P: AsRef<Path>>(path: P, index: ClstrIndex) -> Result<IndexedReader> {
+        let path = path.as_ref();
+        if !index.is_valid_for(path)? {
+            return Err(Error::new(ErrorKind::ReadRecord(format!(
+                "index is out of date for {}",
+                path.display()
+            ))));
+        }
+
+        let mut by_id = HashMap::with_capacity(index.entries.len());
+        for (position, entry) in index.entries.iter().enumerate() {
+            by_id.entry(entry.cluster_id).or_insert(position);
+        }
+
+        Ok(IndexedReader {
+            file: File::open(path)?,
+            index,
+            by_id,
+        })
+    }
+
+    /// Returns the index in use.
+    pub fn index(&self) -> &ClstrIndex {
+        &self.index
+    }
+
+    /// Read the cluster described by `entry`.
+    fn read_entry(&mut self, entry: IndexEntry) -> Result<Cluster> {
+        self.file.seek(SeekFrom::Start(entry.offset))?;
+        let reader = BufReader::new((&self.file).take(entry.len));
+
+        ClstrParser::new(reader).next().unwrap_or_else(|| {
+            Err(Error::new(ErrorKind::ReadRecord(format!(
+                "no cluster at byte offset {}",
+                entry.offset
+            ))))
+        })
+    }
+
+    /// Reads the cluster with the given ID. If the ID occurs more than once,
+    /// the first such cluster is read.
+    pub fn get_cluster(&mut self, cluster_id: usize) -> Result<Cluster> {
+        let entry = match self.by_id.get(&cluster_id) {
+            Some(&position) => self.index.entries[position],
+            None => {
+                return Err(Error::new(ErrorKind::ReadRecord(format!(
+                    "no cluster with ID {cluster_id}"
+                ))))
+            }
+        };
+        self.read_entry(entry)
+    }
+
+    /// Reads every cluster whose ID is in `range`, in file order.
+    pub fn get_clusters<B: RangeBounds<usize>>(&mut self, range: B) -> Result<Vec<Cluster>> {
+        let entries: Vec<IndexEntry> = self
+            .index
+            .entries
+            .iter()
+            .filter(|e| range.contains(&e.cluster_id))
+            .copied()
+            .collect();
+
+        entries.into_iter().map(|e| self.read_entry(e)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_random_access() {
+        let path = crate::tests::temp_path("index.clstr");
+        std::fs::copy(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_nt.clstr"),
+            &path,
+        )
+        .unwrap();
+
+        let index = build_index(&path).unwrap();
+        let ids: Vec<usize> = index.entries().iter().map(|e| e.cluster_id).collect();
+        assert_eq!(ids, vec![476, 477, 478, 479]);
+
+        let sidecar = index_path(&path);
+        assert!(sidecar.to_string_lossy().ends_with(".clstr.cidx"));
+        index.save(&sidecar).unwrap();
+        assert_eq!(ClstrIndex::load(&sidecar).unwrap(), index);
+
+        let mut reader = IndexedReader::open(&path).unwrap();
+        let cluster = reader.get_cluster(479).unwrap();
+        assert_eq!(cluster.cluster_id(), 479);
+        assert_eq!(cluster.size(), 6);
+        assert!(reader.get_cluster(12).is_err());
+
+        let clusters = reader.get_clusters(477..=478).unwrap();
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].cluster_id(), 477);
+        assert_eq!(clusters[1].sequences()[0].length(), 198);
+
+        // a changed file invalidates the index
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        writeln!(file, ">Cluster 480\n0       90nt, >::SUPER_1:1-91... *").unwrap();
+        drop(file);
+        assert!(!index.is_valid_for(&path).unwrap());
+        assert!(IndexedReader::with_index(&path, index).is_err());
+        let mut reader = IndexedReader::open(&path).unwrap();
+        assert_eq!(reader.get_cluster(480).unwrap().size(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&sidecar).unwrap();
+    }
+}
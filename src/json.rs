@@ -0,0 +1,190 @@
+/*!
+Clusters written as JSON and JSONL, and read back from JSONL, enabled by the
+`serde` feature. Each cluster is written as its serde form, such as
+
+```text
+{"cluster_id":0,"sequences":[{"length":4481,"unit":"aa","id":"a","identity":99.89,
+"strand":null,"is_representative":false,"maybe_truncated":false}],"header_note":null}
+```
+
+with floats written in the shortest form that reads back to the same value.
+*/
+
+use std::io::{self, BufRead, Write};
+
+use crate::{parse_error, Cluster, Error, Result};
+
+/// Reads a sequence's identity, rejecting NaN and infinite values as the
+/// parser does.
+pub(crate) fn deserialize_identity<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<f32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let identity = <Option<f32> as serde::Deserialize>::deserialize(deserializer)?;
+    match identity {
+        Some(identity) if !identity.is_finite() => Err(serde::de::Error::custom(format!(
+            "identity {identity} is not a finite number"
+        ))),
+        _ => Ok(identity),
+    }
+}
+
+/// Writes `clusters` to `writer` as a single JSON array, one cluster at a
+/// time. The first read or write error stops the writing, leaving the array
+/// unclosed.
+pub fn to_json<I, W>(clusters: I, mut writer: W) -> Result<()>
+where
+    I: IntoIterator<Item = Result<Cluster>>,
+    W: Write,
+{
+    writer.write_all(b"[")?;
+    for (i, cluster) in clusters.into_iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut writer, &cluster?).map_err(io::Error::from)?;
+    }
+    writer.write_all(b"]\n")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes `clusters` to `writer` as JSONL, one cluster object per line, one
+/// cluster at a time. The first read or write error stops the writing.
+pub fn to_jsonl<I, W>(clusters: I, mut writer: W) -> Result<()>
+where
+    I: IntoIterator<Item = Result<Cluster>>,
+    W: Write,
+{
+    for cluster in clusters {
+        serde_json::to_writer(&mut writer, &cluster?).map_err(io::Error::from)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Function to read the clusters written by [`to_jsonl`] from a reader.
+pub fn from_jsonl<R: BufRead>(reader: R) -> JsonlParser<R> {
+    JsonlParser {
+        reader,
+        buf: String::new(),
+        line: 0,
+    }
+}
+
+/// Iterator over the clusters of a JSONL file, one per line, returned by
+/// [`from_jsonl`]. Blank lines are skipped, and a line that is not a cluster
+/// is a parse error at that line.
+pub struct JsonlParser<R: BufRead> {
+    /// The reader to parse the file.
+    reader: R,
+    /// Buffer holding the line currently being parsed.
+    buf: String,
+    /// The 1-based number of the last line read.
+    line: u64,
+}
+
+impl<R: BufRead> JsonlParser<R> {
+    /// Returns the 1-based number of the last line read, or 0 if nothing has
+    /// been read yet.
+    pub fn line_number(&self) -> u64 {
+        self.line
+    }
+}
+
+impl<R: BufRead> Iterator for JsonlParser<R> {
+    type Item = Result<Cluster>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_line(&mut self.buf) {
+                Ok(0) => return None,
+                Ok(_) => self.line += 1,
+                Err(e) => return Some(Err(Error::from(e))),
+            }
+            if self.buf.trim().is_empty() {
+                continue;
+            }
+
+            return Some(serde_json::from_str(&self.buf).map_err(|e| {
+                // the location is given separately
+                let msg = e.to_string();
+                let msg = msg.rsplit_once(" at line ").map_or(&*msg, |(msg, _)| msg);
+                parse_error(self.line, Some(e.column()), msg.to_string())
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClstrParser, ErrorKind, ParseOptions};
+
+    #[test]
+    fn test_json_round_trip() {
+        let data = ">Cluster 0 phage-like
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+>Cluster 479
+0       122nt, >::SUPER_5:20757114-20757236... at -/97.54%
+1       198nt, >::SUPER_2:18124787-18124985... *
+";
+        let parse = || {
+            ClstrParser::with_options(
+                data.as_bytes(),
+                ParseOptions {
+                    cd_hit_est: true,
+                    ..ParseOptions::default()
+                },
+            )
+        };
+        let clusters: Vec<Cluster> = parse().collect::<Result<_>>().unwrap();
+
+        let mut jsonl = Vec::new();
+        to_jsonl(parse(), &mut jsonl).unwrap();
+        let jsonl = String::from_utf8(jsonl).unwrap();
+        assert_eq!(jsonl.lines().count(), 2);
+        assert!(jsonl.starts_with(r#"{"cluster_id":0,"sequences":[{"length":4481,"unit":"aa","id":"sp|P0C6T5|R1A_BCHK5","identity":99.89,"#));
+        assert!(jsonl.contains(r#""strand":"reverse""#));
+        assert!(jsonl.contains(r#""header_note":"phage-like""#));
+
+        // with a blank line, as left by hand editing
+        let read: Vec<Cluster> = from_jsonl(jsonl.replace("}\n{", "}\n\n{").as_bytes())
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(read, clusters);
+        assert_eq!(read[0].get("sp|P0C6W4|R1AB_BCHK5").unwrap().length(), 7182);
+
+        let mut json = Vec::new();
+        to_json(parse(), &mut json).unwrap();
+        let array: Vec<Cluster> = serde_json::from_slice(&json).unwrap();
+        assert_eq!(array, clusters);
+
+        let mut empty = Vec::new();
+        to_json(crate::from_str(""), &mut empty).unwrap();
+        assert_eq!(empty, b"[]\n");
+
+        let mut bad = from_jsonl("\n{\"cluster_id\": 1}\n".as_bytes());
+        match bad.next().unwrap().unwrap_err().kind() {
+            ErrorKind::Parse { line, msg, .. } => {
+                assert_eq!(*line, 2);
+                assert!(msg.contains("sequences"), "{msg}");
+            }
+            kind => panic!("unexpected error {kind:?}"),
+        }
+        assert!(to_jsonl(crate::from_str(">Cluster x\n"), Vec::new()).is_err());
+
+        // too large for an f32, so infinite
+        let infinite = r#"{"length":1,"unit":"aa","id":"a","identity":1e39,"strand":null,"is_representative":false,"maybe_truncated":false}"#;
+        let err = serde_json::from_str::<crate::Sequence>(infinite).unwrap_err();
+        assert!(err.to_string().contains("not a finite number"), "{err}");
+        let finite = infinite.replace("1e39", "99.5");
+        let seq: crate::Sequence = serde_json::from_str(&finite).unwrap();
+        assert_eq!(seq.identity(), Some(99.5));
+    }
+}
@@ -3,10 +3,59 @@ A small crate to parse CD-HIT's .clstr file format. *Only tested with CD-HIT, no
 Or actually another program in the `cd-hit` suite.
 */
 
+use flate2::bufread::MultiGzDecoder;
+use flate2::write::GzEncoder;
+/// The compression level taken by [`to_gz_path_with_level`].
+pub use flate2::Compression;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Write};
 use std::num::{ParseFloatError, ParseIntError};
+use std::ops::RangeBounds;
 use std::path::Path;
+use std::sync::OnceLock;
+
+mod file;
+pub use file::{merge_all, ClstrFile, IndexedClstrFile};
+
+mod set;
+pub use set::ClusterSet;
+
+mod compare;
+pub use compare::{compare, ComparisonReport, PairCounts};
+
+mod summary;
+pub use summary::{
+    identity_histogram, size_distribution, summarize, ClstrSummary, IdentityHistogram,
+    SizeDistribution,
+};
+
+mod validate;
+pub use validate::{find_duplicate_ids, ValidationWarning};
+
+mod index;
+pub use index::{build_index, index_path, ClstrIndex, IndexEntry, IndexedReader};
+
+mod two_d;
+pub use two_d::{ClstrParser2D, TwoDCluster, TwoDSequence};
+
+#[cfg(feature = "async")]
+mod async_parser;
+#[cfg(feature = "async")]
+pub use async_parser::AsyncClstrParser;
+
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "serde")]
+pub use json::{from_jsonl, to_json, to_jsonl, JsonlParser};
+
+#[cfg(feature = "parallel")]
+mod parallel;
+#[cfg(feature = "parallel")]
+pub use parallel::{par_from_path, par_from_path_with_options};
 
 /// A type alias for `Result<T, clstr::Error>`.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -29,12 +78,42 @@ impl Error {
     }
 }
 
+/// The kinds of [`Error`]. More may be added without a major version bump.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ErrorKind {
     Io(io::Error),
     Int(ParseIntError),
     Float(ParseFloatError),
     ReadRecord(String),
+    /// A malformed line in the input, located by its 1-based line number and,
+    /// where known, the 1-based column of the offending token.
+    Parse {
+        line: u64,
+        column: Option<usize>,
+        msg: String,
+    },
+    /// A sequence written with a different unit from the rest of the output,
+    /// which [`ClstrWriter`] refuses unless mixed units are allowed.
+    MixedUnits {
+        cluster_id: usize,
+        expected: LengthUnit,
+        found: LengthUnit,
+    },
+    /// A sequence ID that is not in the cluster it was looked up in.
+    MissingSequence {
+        cluster_id: usize,
+        id: String,
+    },
+    /// A cluster that [`ClstrWriter`] cannot write without corrupting the
+    /// output, such as one with an empty sequence ID or a line break in an ID.
+    InvalidCluster {
+        cluster_id: usize,
+        msg: String,
+    },
+    /// An identity set through [`Sequence::set_identity`] that is not a
+    /// percentage from 0 to 100.
+    InvalidIdentity(f32),
 }
 
 impl From<io::Error> for Error {
@@ -62,20 +141,94 @@ impl std::fmt::Display for Error {
             ErrorKind::Int(ref err) => write!(f, "parsing integer error - {err}"),
             ErrorKind::Float(ref err) => write!(f, "parsing float error - {err}"),
             ErrorKind::ReadRecord(ref err) => write!(f, "reading record - {err}"),
+            ErrorKind::Parse {
+                line,
+                column: Some(column),
+                ref msg,
+            } => write!(f, "parse error at line {line}, column {column}: {msg}"),
+            ErrorKind::Parse {
+                line,
+                column: None,
+                ref msg,
+            } => write!(f, "parse error at line {line}: {msg}"),
+            ErrorKind::MixedUnits {
+                cluster_id,
+                expected,
+                found,
+            } => write!(
+                f,
+                "writing cluster {cluster_id} - expected {expected} sequences, found {found}"
+            ),
+            ErrorKind::MissingSequence { cluster_id, ref id } => {
+                write!(f, "cluster {cluster_id} has no sequence '{id}'")
+            }
+            ErrorKind::InvalidCluster {
+                cluster_id,
+                ref msg,
+            } => write!(f, "writing cluster {cluster_id} - {msg}"),
+            ErrorKind::InvalidIdentity(identity) => {
+                write!(f, "identity {identity}% is outside 0 to 100%")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self.0 {
+            ErrorKind::Io(ref err) => Some(err),
+            ErrorKind::Int(ref err) => Some(err),
+            ErrorKind::Float(ref err) => Some(err),
+            ErrorKind::ReadRecord(_)
+            | ErrorKind::Parse { .. }
+            | ErrorKind::MixedUnits { .. }
+            | ErrorKind::MissingSequence { .. }
+            | ErrorKind::InvalidCluster { .. }
+            | ErrorKind::InvalidIdentity(_) => None,
         }
     }
 }
 
-impl std::error::Error for Error {}
+/// Build an `ErrorKind::Parse` error.
+fn parse_error(line: u64, column: Option<usize>, msg: String) -> Error {
+    Error::new(ErrorKind::Parse { line, column, msg })
+}
 
-#[derive(Debug, Clone, Copy)]
+/// The unit of a sequence length, telling protein from DNA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum LengthUnit {
+    /// Amino acids, written `aa`.
     Aa,
+    /// Nucleotides, written `nt`.
     Nt,
 }
 
-/// Represents a single sequence entry in a cluster.
-#[derive(Debug)]
+impl std::fmt::Display for LengthUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LengthUnit::Aa => write!(f, "aa"),
+            LengthUnit::Nt => write!(f, "nt"),
+        }
+    }
+}
+
+/// The strand a CD-HIT-EST sequence aligned to its representative on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Strand {
+    /// The same strand as the representative, written `+`.
+    Forward,
+    /// The reverse complement, written `-`.
+    Reverse,
+}
+
+/// Represents a single sequence entry in a cluster. Equality compares the
+/// identity exactly; see [`Sequence::approx_eq`] for a tolerant comparison.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sequence {
     /// The length of the sequence.
     length: u32,
@@ -83,10 +236,19 @@ pub struct Sequence {
     unit: LengthUnit,
     /// The sequence ID.
     id: String,
-    /// The percentage identity to the representative sequence.
+    /// The percentage identity to the representative sequence, never NaN
+    /// or infinite.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, deserialize_with = "json::deserialize_identity")
+    )]
     identity: Option<f32>,
+    /// The strand of the alignment to the representative, from CD-HIT-EST.
+    strand: Option<Strand>,
     /// Whether this sequence is the representative sequence.
     is_representative: bool,
+    /// Whether the ID is long enough that CD-HIT may have cut it short.
+    maybe_truncated: bool,
 }
 
 impl Sequence {
@@ -95,6 +257,11 @@ impl Sequence {
         self.length
     }
 
+    /// Returns whether the length is in amino acids or nucleotides.
+    pub fn unit(&self) -> LengthUnit {
+        self.unit
+    }
+
     /// Returns the sequence ID.
     pub fn id(&self) -> &str {
         &self.id
@@ -105,19 +272,214 @@ impl Sequence {
         self.identity
     }
 
+    /// Returns the strand of the alignment to the representative, if parsed
+    /// with [`ParseOptions::cd_hit_est`] set.
+    pub fn strand(&self) -> Option<Strand> {
+        self.strand
+    }
+
     /// Returns whether this sequence is the representative sequence.
     pub fn is_representative(&self) -> bool {
         self.is_representative
     }
+
+    /// Returns whether the ID reaches [`ParseOptions::description_length`],
+    /// in which case CD-HIT may have cut it short and it may not match the
+    /// name in the original FASTA file.
+    pub fn maybe_truncated(&self) -> bool {
+        self.maybe_truncated
+    }
+
+    /// Sets the length of the sequence.
+    pub fn set_length(&mut self, length: u32) {
+        self.length = length;
+    }
+
+    /// Sets the sequence ID.
+    pub fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+
+    /// Sets the percentage identity to the representative sequence, failing
+    /// and leaving it as it was if the identity is not from 0 to 100.
+    pub fn set_identity(&mut self, identity: Option<f32>) -> Result<()> {
+        if let Some(identity) = identity {
+            if !(0.0..=100.0).contains(&identity) {
+                return Err(Error::new(ErrorKind::InvalidIdentity(identity)));
+            }
+        }
+        self.identity = identity;
+        Ok(())
+    }
+
+    /// Sets the strand of the alignment to the representative.
+    pub fn set_strand(&mut self, strand: Option<Strand>) {
+        self.strand = strand;
+    }
+
+    /// Sets whether this sequence is the representative sequence. Prefer
+    /// [`Cluster::set_representative`], which keeps one per cluster.
+    pub fn set_representative(&mut self, is_representative: bool) {
+        self.is_representative = is_representative;
+    }
+
+    /// Parses a single member line such as
+    /// `0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%`, with the default
+    /// [`ParseOptions`]. The index is not kept, and errors are located as if
+    /// the line were the first of a file.
+    pub fn parse_line(line: &str) -> Result<Sequence> {
+        Sequence::parse_line_with_options(line, &ParseOptions::default())
+    }
+
+    /// Parses a single member line as [`Sequence::parse_line`] does, with
+    /// the given options.
+    pub fn parse_line_with_options(line: &str, options: &ParseOptions) -> Result<Sequence> {
+        let (_, seq) = parse_sequence_line(line.trim_ascii_end(), 1, options)?;
+        options.check_sequence(&seq, 1)?;
+        Ok(seq)
+    }
+
+    /// Returns whether this sequence equals `other`, allowing the identities
+    /// to differ by up to `epsilon`, as they may after a round trip through
+    /// [`ClstrWriter`].
+    pub fn approx_eq(&self, other: &Sequence, epsilon: f32) -> bool {
+        let identity_eq = match (self.identity, other.identity) {
+            (Some(a), Some(b)) => (a - b).abs() <= epsilon,
+            (a, b) => a == b,
+        };
+
+        identity_eq
+            && self.length == other.length
+            && self.unit == other.unit
+            && self.id == other.id
+            && self.strand == other.strand
+            && self.is_representative == other.is_representative
+            && self.maybe_truncated == other.maybe_truncated
+    }
 }
 
-/// Represents a cluster containing multiple sequences.
-#[derive(Debug)]
+// the parser, `set_identity` and deserialization all reject NaN identities
+impl Eq for Sequence {}
+
+/// Sequences hash by their ID alone, while equality still compares every
+/// field, so a set can hold entries for one ID that differ in identity or
+/// representative flag, as the same sequence does across clusters.
+impl Hash for Sequence {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+/// Formats the sequence as its member line in a `.clstr` file, without the
+/// leading index or the line break, e.g. `4481aa, >sp|P0C6T5|R1A_BCHK5... *`.
+impl std::fmt::Display for Sequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}, >{}...", self.length, self.unit, self.id)?;
+
+        if let Some(identity) = self.identity {
+            match self.strand {
+                Some(Strand::Forward) => write!(f, " at +/{identity:.2}%")?,
+                Some(Strand::Reverse) => write!(f, " at -/{identity:.2}%")?,
+                None => write!(f, " at {identity:.2}%")?,
+            }
+        }
+
+        // CD-HIT separates the marker from the ellipsis with a space, as
+        // in `>sp|P0C6W4|R1AB_BCHK5... *`
+        if self.is_representative {
+            write!(f, " *")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Where [`Cluster::sort_by_key`] and the other sorting methods put the
+/// representative.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RepresentativePlacement {
+    /// Before every other sequence.
+    #[default]
+    First,
+    /// After every other sequence.
+    Last,
+    /// Wherever it sorts to.
+    Sorted,
+}
+
+/// The direction of [`Cluster::sort_by_length`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Smallest first.
+    #[default]
+    Ascending,
+    /// Largest first.
+    Descending,
+}
+
+/// Length and identity statistics over a cluster, returned by
+/// [`Cluster::stats`]. The length fields are all zero for an empty cluster.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClusterStats {
+    /// The summed length of all sequences.
+    pub total_length: u64,
+    /// The mean sequence length.
+    pub mean_length: f64,
+    /// The median sequence length, the mean of the middle two for an even
+    /// number of sequences.
+    pub median_length: f64,
+    /// The shortest sequence length.
+    pub min_length: u32,
+    /// The longest sequence length.
+    pub max_length: u32,
+    /// The mean identity to the representative over the other sequences, or
+    /// `None` if none of them have one, as in a singleton.
+    pub mean_identity: Option<f32>,
+    /// The lowest identity to the representative over the other sequences.
+    pub min_identity: Option<f32>,
+    /// The length of the representative, if available.
+    pub representative_length: Option<u32>,
+}
+
+/// Represents a cluster containing multiple sequences. Clusters hash by
+/// their cluster ID and sequence IDs alone.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cluster {
     /// The cluster ID.
     cluster_id: usize,
     /// The sequences in this cluster.
     sequences: Vec<Sequence>,
+    /// Any text after the number in the header, such as `phage-like` in
+    /// `>Cluster 17 phage-like`.
+    header_note: Option<String>,
+    /// The positions of the sequences sorted by ID, built on the first
+    /// [`Cluster::get`] and dropped whenever the sequences may change.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    id_index: IdIndex,
+}
+
+/// The lazily built ID index of a [`Cluster`]. It is left out of equality
+/// and debug output, being derived from the sequences.
+#[derive(Clone, Default)]
+struct IdIndex(OnceLock<Vec<usize>>);
+
+impl IdIndex {
+    fn clear(&mut self) {
+        self.0.take();
+    }
+}
+
+impl PartialEq for IdIndex {
+    fn eq(&self, _: &IdIndex) -> bool {
+        true
+    }
+}
+
+impl std::fmt::Debug for IdIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("IdIndex")
+    }
 }
 
 impl Cluster {
@@ -126,299 +488,3807 @@ impl Cluster {
         self.cluster_id
     }
 
+    /// Sets the cluster ID.
+    pub fn set_cluster_id(&mut self, cluster_id: usize) {
+        self.cluster_id = cluster_id;
+    }
+
+    /// Returns the cluster in `.clstr` format, as its [`Display`] does.
+    ///
+    /// [`Display`]: std::fmt::Display
+    pub fn to_clstr_string(&self) -> String {
+        self.to_string()
+    }
+
+    /// Returns the text following the cluster number in the header, if any.
+    pub fn header_note(&self) -> Option<&str> {
+        self.header_note.as_deref()
+    }
+
     /// Returns the sequences in this cluster.
-    pub fn sequences(&self) -> &Vec<Sequence> {
+    pub fn sequences(&self) -> &[Sequence] {
         &self.sequences
     }
 
+    /// Returns an iterator over the sequences in this cluster.
+    pub fn iter(&self) -> std::slice::Iter<'_, Sequence> {
+        self.sequences.iter()
+    }
+
+    /// Returns an iterator over the sequences in this cluster that allows
+    /// modifying them.
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Sequence> {
+        self.sequences_mut().iter_mut()
+    }
+
     /// Returns the representative sequence, if available.
     pub fn get_representative(&self) -> Option<&Sequence> {
         self.sequences.iter().find(|s| s.is_representative)
     }
 
+    /// Returns the representative sequence, or the longest sequence as
+    /// [`Cluster::longest`] picks it if there is no representative, as when
+    /// the `*` line was edited out.
+    pub fn representative_or_longest(&self) -> Option<&Sequence> {
+        self.get_representative().or_else(|| self.longest())
+    }
+
+    /// Returns the longest sequence, taking the lowest ID of those
+    /// equally long, or `None` if the cluster is empty.
+    pub fn longest(&self) -> Option<&Sequence> {
+        self.sequences
+            .iter()
+            .max_by(|a, b| a.length.cmp(&b.length).then_with(|| b.id.cmp(&a.id)))
+    }
+
+    /// Returns the shortest sequence, taking the lowest ID of those
+    /// equally short, or `None` if the cluster is empty.
+    pub fn shortest(&self) -> Option<&Sequence> {
+        self.sequences
+            .iter()
+            .min_by(|a, b| a.length.cmp(&b.length).then_with(|| a.id.cmp(&b.id)))
+    }
+
+    /// Returns an edge from the representative to each other sequence, in
+    /// order, or nothing if there is no representative.
+    pub fn to_edges(&self) -> impl Iterator<Item = Edge<'_>> {
+        self.edges_from(self.get_representative())
+    }
+
+    /// Returns an edge from `hub`, one of this cluster's sequences, to each
+    /// other sequence.
+    fn edges_from<'a>(&'a self, hub: Option<&'a Sequence>) -> impl Iterator<Item = Edge<'a>> {
+        hub.into_iter().flat_map(move |hub| {
+            self.sequences
+                .iter()
+                .filter(move |s| !std::ptr::eq(*s, hub))
+                .map(move |s| Edge {
+                    rep_id: hub.id(),
+                    member_id: s.id(),
+                    identity: s.identity,
+                })
+        })
+    }
+
+    /// Returns whether this cluster has exactly one sequence.
+    pub fn is_singleton(&self) -> bool {
+        self.sequences.len() == 1
+    }
+
+    /// Returns whether this cluster is a singleton of the shape CD-HIT
+    /// writes: its one sequence is the representative, with no identity.
+    /// A one-member cluster that is not, as after hand editing, is still a
+    /// [`Cluster::is_singleton`].
+    pub fn is_plain_singleton(&self) -> bool {
+        matches!(
+            self.sequences.as_slice(),
+            [seq] if seq.is_representative && seq.identity.is_none()
+        )
+    }
+
     /// Returns the number of sequences in this cluster.
     pub fn size(&self) -> usize {
         self.sequences.len()
     }
-}
 
-/// Iterator to parse `.clstr` file.
-pub struct ClstrParser<R: BufRead> {
-    /// The reader to parse the file.
-    reader: R,
-    /// The current cluster being parsed.
-    current_cluster: Option<Cluster>,
-}
+    /// Returns the summed length of the sequences in this cluster.
+    pub fn total_sequence_length(&self) -> u64 {
+        self.sequences.iter().map(|s| s.length() as u64).sum()
+    }
 
-impl<R: BufRead> ClstrParser<R> {
-    pub fn new(reader: R) -> Self {
-        ClstrParser {
-            reader,
-            current_cluster: None,
+    /// Returns the length of the representative sequence, if available.
+    pub fn representative_length(&self) -> Option<u32> {
+        self.get_representative().map(Sequence::length)
+    }
+
+    /// Computes length and identity statistics over this cluster.
+    pub fn stats(&self) -> ClusterStats {
+        let mut lengths: Vec<u32> = self.sequences.iter().map(Sequence::length).collect();
+        lengths.sort_unstable();
+
+        let total_length = self.total_sequence_length();
+        let n = lengths.len();
+        let mean_length = if n == 0 {
+            0.0
+        } else {
+            total_length as f64 / n as f64
+        };
+        let median_length = match n {
+            0 => 0.0,
+            _ if n.is_multiple_of(2) => (lengths[n / 2 - 1] as f64 + lengths[n / 2] as f64) / 2.0,
+            _ => lengths[n / 2] as f64,
+        };
+
+        ClusterStats {
+            total_length,
+            mean_length,
+            median_length,
+            min_length: lengths.first().copied().unwrap_or(0),
+            max_length: lengths.last().copied().unwrap_or(0),
+            mean_identity: self.average_identity(),
+            min_identity: self.min_identity(),
+            representative_length: self.representative_length(),
         }
     }
-}
 
-impl<R: BufRead> Iterator for ClstrParser<R> {
-    type Item = Result<Cluster>;
+    /// Returns the identities of the non-representative sequences that have
+    /// one.
+    fn member_identities(&self) -> impl Iterator<Item = f32> + '_ {
+        self.sequences
+            .iter()
+            .filter(|s| !s.is_representative)
+            .filter_map(|s| s.identity)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        for line_result in self.reader.by_ref().lines() {
-            let line = match line_result {
-                Ok(l) => l,
-                Err(e) => return Some(Err(Error::from(e))),
-            };
+    /// Returns the mean identity to the representative over the other
+    /// sequences, or `None` if none of them have an identity.
+    pub fn average_identity(&self) -> Option<f32> {
+        let (sum, count) = self
+            .member_identities()
+            .fold((0.0, 0usize), |(sum, count), i| (sum + i, count + 1));
+        (count > 0).then(|| sum / count as f32)
+    }
 
-            if line.starts_with('>') {
-                if let Some(c) = self.current_cluster.take() {
-                    self.current_cluster = Some(Cluster {
-                        cluster_id: c.cluster_id + 1,
-                        sequences: Vec::new(),
-                    });
-                    return Some(Ok(c));
-                }
+    /// Returns the lowest identity to the representative, or `None` if no
+    /// sequence has an identity.
+    pub fn min_identity(&self) -> Option<f32> {
+        self.member_identities().reduce(f32::min)
+    }
 
-                self.current_cluster = Some(Cluster {
-                    cluster_id: self
-                        .current_cluster
-                        .as_ref()
-                        .map_or(0, |c| c.cluster_id + 1),
-                    sequences: Vec::new(),
-                });
-            } else if let Some(ref mut c) = self.current_cluster {
-                match parse_sequence_line(&line) {
-                    Ok(seq) => c.sequences.push(seq),
-                    Err(e) => return Some(Err(e)),
-                }
-            }
-        }
+    /// Returns the highest identity to the representative, or `None` if no
+    /// sequence has an identity.
+    pub fn max_identity(&self) -> Option<f32> {
+        self.member_identities().reduce(f32::max)
+    }
 
-        self.current_cluster.take().map(Ok)
+    /// Returns the first sequence with the given ID, if present. The same as
+    /// [`Cluster::get`].
+    pub fn find_sequence(&self, id: &str) -> Option<&Sequence> {
+        self.get(id)
     }
-}
 
-/// Parse a single sequence line from a cluster file.
-fn parse_sequence_line(line: &str) -> Result<Sequence> {
-    let parts: Vec<&str> = line.split_whitespace().collect();
-    if parts.len() < 3 {
-        return Err(Error::new(ErrorKind::ReadRecord(format!(
-            "Invalid sequence line: {line}"
-        ))));
+    /// Returns whether a sequence with the given ID is in this cluster. The
+    /// same as [`Cluster::contains`].
+    pub fn contains_id(&self, id: &str) -> bool {
+        self.contains(id)
     }
 
-    // parts[1] is something like "4481aa," or "100nt,"
-    let len_token = parts[1].trim_end_matches(',');
-    let split_pos = len_token
-        .find(|c: char| !c.is_ascii_digit())
-        .unwrap_or(len_token.len());
-    let (len_num, len_unit) = len_token.split_at(split_pos);
+    /// Returns the first sequence with the given ID, if present. This sorts
+    /// an index of the IDs on first use, keeping it until the sequences are
+    /// next modified, so repeated lookups on a large cluster take
+    /// logarithmic time.
+    pub fn get(&self, id: &str) -> Option<&Sequence> {
+        let index = self.id_index.0.get_or_init(|| {
+            let mut index: Vec<usize> = (0..self.sequences.len()).collect();
+            index.sort_by(|&a, &b| self.sequences[a].id.cmp(&self.sequences[b].id));
+            index
+        });
 
-    let length = len_num.parse::<u32>()?;
+        // the sort is stable, so the first match is the first occurrence
+        let first = index.partition_point(|&i| self.sequences[i].id.as_str() < id);
+        index
+            .get(first)
+            .map(|&i| &self.sequences[i])
+            .filter(|s| s.id == id)
+    }
 
-    let unit = match len_unit {
-        "aa" => LengthUnit::Aa,
-        "nt" => LengthUnit::Nt,
-        other => {
-            return Err(Error::new(ErrorKind::ReadRecord(format!(
-                "Unknown length unit {other} in line: {line}"
-            ))))
+    /// Returns whether a sequence with the given ID is in this cluster,
+    /// using the index of [`Cluster::get`].
+    pub fn contains(&self, id: &str) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Returns the number of distinct sequence IDs in both this cluster and
+    /// `other`.
+    pub fn overlap_count(&self, other: &Cluster) -> usize {
+        let ids: HashSet<&str> = self.iter().map(Sequence::id).collect();
+        other
+            .iter()
+            .map(Sequence::id)
+            .collect::<HashSet<&str>>()
+            .intersection(&ids)
+            .count()
+    }
+
+    /// Returns the Jaccard index of the sequence IDs of this cluster and
+    /// `other`: the number in both over the number in either. Two empty
+    /// clusters are taken to be identical, with an index of 1.
+    pub fn jaccard_similarity(&self, other: &Cluster) -> f64 {
+        let ids: HashSet<&str> = self.iter().map(Sequence::id).collect();
+        let other_ids: HashSet<&str> = other.iter().map(Sequence::id).collect();
+        let shared = ids.intersection(&other_ids).count();
+        let union = ids.len() + other_ids.len() - shared;
+        if union == 0 {
+            1.0
+        } else {
+            shared as f64 / union as f64
         }
-    };
+    }
 
-    // ID parsing unchanged for now
-    let id = parts[2]
-        .trim_start_matches('>')
-        .split("...")
-        .next()
-        .ok_or_else(|| Error::new(ErrorKind::ReadRecord(format!("Invalid ID format: {line}"))))?
-        .to_string();
+    /// Returns whether this cluster equals `other`, comparing sequences with
+    /// [`Sequence::approx_eq`].
+    pub fn approx_eq(&self, other: &Cluster, epsilon: f32) -> bool {
+        self.cluster_id == other.cluster_id
+            && self.header_note == other.header_note
+            && self.sequences.len() == other.sequences.len()
+            && self
+                .sequences
+                .iter()
+                .zip(&other.sequences)
+                .all(|(a, b)| a.approx_eq(b, epsilon))
+    }
 
-    let is_representative = line.ends_with('*');
+    /// Appends a sequence to this cluster as it is, representative flag
+    /// included; use [`Cluster::set_representative`] to move the flag.
+    pub fn push_sequence(&mut self, sequence: Sequence) {
+        self.sequences_mut().push(sequence);
+    }
 
-    let identity = parse_identity(line)?;
+    /// Removes and returns the first sequence with the given ID, if present.
+    /// No other sequence is promoted when the representative is removed, so
+    /// [`Cluster::get_representative`] returns `None` until one is set.
+    pub fn remove_sequence(&mut self, id: &str) -> Option<Sequence> {
+        let position = self.sequences.iter().position(|s| s.id == id)?;
+        Some(self.sequences_mut().remove(position))
+    }
 
-    Ok(Sequence {
-        length,
-        unit,
-        id,
-        identity,
-        is_representative,
-    })
-}
+    /// Removes every sequence whose ID occurred earlier in the cluster and
+    /// returns them, in order. The first occurrence of each ID is kept, so a
+    /// representative listed twice leaves the cluster without one only if
+    /// the first occurrence was not marked.
+    pub fn dedup_by_id(&mut self) -> Vec<Sequence> {
+        let mut seen = HashSet::new();
+        let mut removed = Vec::new();
+        let mut kept = Vec::with_capacity(self.sequences.len());
+        for seq in self.sequences_mut().drain(..) {
+            if seen.contains(seq.id.as_str()) {
+                removed.push(seq);
+            } else {
+                seen.insert(seq.id.clone());
+                kept.push(seq);
+            }
+        }
+        self.sequences = kept;
+        removed
+    }
 
-fn parse_identity(line: &str) -> Result<Option<f32>> {
-    let at_pos = match line.find(" at ") {
-        Some(p) => p,
-        None => return Ok(None),
-    };
+    /// Keeps only the sequences for which `f` returns `true`, in order. As
+    /// with [`Cluster::remove_sequence`], dropping the representative leaves
+    /// the cluster without one, and `true` is returned if that happened.
+    /// Like [`Cluster::drain`], this takes `&mut self`, as the other
+    /// mutators do, rather than consuming and returning the cluster.
+    pub fn retain<F: FnMut(&Sequence) -> bool>(&mut self, f: F) -> bool {
+        let had_representative = self.get_representative().is_some();
+        self.sequences_mut().retain(f);
+        had_representative && self.get_representative().is_none()
+    }
 
-    // Take the token immediately after " at "
-    let rest = &line[at_pos + 4..];
-    let token = rest
-        .split_whitespace()
-        .next()
-        .unwrap_or("")
-        .trim_end_matches('%')
-        .trim();
+    /// Removes the sequences in `range` and returns them, in order, with
+    /// `true` if the representative was among them, leaving the cluster
+    /// without one, as [`Cluster::retain`] reports it. This takes
+    /// `&mut self` rather than consuming and returning the cluster.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds, as [`Vec::drain`] does.
+    pub fn drain<B: RangeBounds<usize>>(&mut self, range: B) -> (Vec<Sequence>, bool) {
+        let drained: Vec<Sequence> = self.sequences_mut().drain(range).collect();
+        let removed_representative = drained.iter().any(|s| s.is_representative);
+        (drained, removed_representative)
+    }
 
-    if token.is_empty() {
-        return Ok(None);
+    /// Makes the first sequence with the given ID the representative,
+    /// clearing the flag on any other sequence. A representative has no
+    /// identity to itself, so the identity and strand of the new one become
+    /// `None`. Fails, changing nothing, if no sequence has the ID.
+    pub fn set_representative(&mut self, id: &str) -> Result<()> {
+        let position = self
+            .sequences
+            .iter()
+            .position(|s| s.id == id)
+            .ok_or_else(|| {
+                Error::new(ErrorKind::MissingSequence {
+                    cluster_id: self.cluster_id,
+                    id: id.to_string(),
+                })
+            })?;
+
+        for (i, seq) in self.sequences.iter_mut().enumerate() {
+            seq.is_representative = i == position;
+        }
+        let rep = &mut self.sequences[position];
+        rep.identity = None;
+        rep.strand = None;
+        Ok(())
     }
 
-    // Handle tokens that contain "/", e.g.
-    //   "-/97.54"
-    //   "+/95.70"
-    //   "99.89/100"
-    if let Some((a, b)) = token.split_once('/') {
-        let a = a.trim();
-        let b = b.trim();
+    /// Clears the representative flag on every sequence.
+    pub fn clear_representative(&mut self) {
+        for seq in &mut self.sequences {
+            seq.is_representative = false;
+        }
+    }
 
-        // cd-hit-est style: "-/97.54" or "+/95.70"
-        // Use the numeric part (b) as identity.
-        let primary = if a == "-" || a == "+" || a.is_empty() {
-            b
-        } else {
-            // e.g. "99.89/100" – use the first numeric part
-            a
-        };
+    /// Returns the sequences in this cluster for modification.
+    pub fn sequences_mut(&mut self) -> &mut Vec<Sequence> {
+        self.id_index.clear();
+        &mut self.sequences
+    }
 
-        return parse_identity_token(primary);
+    /// Appends the sequences of `other` to this cluster, keeping this
+    /// cluster's ID and header note. Only one representative is kept: this
+    /// cluster's if it has one, otherwise `other`'s. A representative of
+    /// `other` that is demoted has no identity to the one kept, so its
+    /// identity and strand become `None`.
+    pub fn merge(mut self, other: Cluster) -> Cluster {
+        let has_representative = self.get_representative().is_some();
+        self.sequences_mut()
+            .extend(other.sequences.into_iter().map(|mut seq| {
+                if seq.is_representative && has_representative {
+                    seq.is_representative = false;
+                    seq.identity = None;
+                    seq.strand = None;
+                }
+                seq
+            }));
+        self
     }
 
-    // Simple case: "99.89"
-    parse_identity_token(token)
-}
+    /// Splits this cluster into the sequences for which `f` returns `true`,
+    /// given the ID `true_id`, and the rest, given `false_id`. The
+    /// representative stays a representative on whichever side it falls,
+    /// leaving the other side without one; either side may be empty.
+    pub fn partition_by<F: FnMut(&Sequence) -> bool>(
+        self,
+        true_id: usize,
+        false_id: usize,
+        f: F,
+    ) -> (Cluster, Cluster) {
+        let (matching, rest) = self.sequences.into_iter().partition(f);
+        (
+            Cluster {
+                cluster_id: true_id,
+                sequences: matching,
+                header_note: None,
+                id_index: IdIndex::default(),
+            },
+            Cluster {
+                cluster_id: false_id,
+                sequences: rest,
+                header_note: None,
+                id_index: IdIndex::default(),
+            },
+        )
+    }
 
-fn parse_identity_token(token: &str) -> Result<Option<f32>> {
-    let t = token.trim();
+    /// Sorts the sequences with `compare`, keeping the order of equal
+    /// sequences, then places the representative. The writer numbers
+    /// sequences in the new order.
+    pub fn sort_by<F>(&mut self, compare: F, placement: RepresentativePlacement)
+    where
+        F: FnMut(&Sequence, &Sequence) -> std::cmp::Ordering,
+    {
+        self.sequences_mut().sort_by(compare);
 
-    // Strip leading "+"
-    let t = t.trim_start_matches('+');
+        let Some(position) = self.sequences.iter().position(|s| s.is_representative) else {
+            return;
+        };
+        match placement {
+            RepresentativePlacement::First => self.sequences[..=position].rotate_right(1),
+            RepresentativePlacement::Last => self.sequences[position..].rotate_left(1),
+            RepresentativePlacement::Sorted => {}
+        }
+    }
 
-    // A bare "-" means "no similarity"
-    if t == "-" || t.is_empty() {
-        return Ok(None);
+    /// Sorts the sequences by the key `f` returns, as [`Cluster::sort_by`]
+    /// does.
+    pub fn sort_by_key<K: Ord, F: FnMut(&Sequence) -> K>(
+        &mut self,
+        mut f: F,
+        placement: RepresentativePlacement,
+    ) {
+        self.sort_by(|a, b| f(a).cmp(&f(b)), placement);
     }
 
-    let value = t.parse::<f32>()?;
-    Ok(Some(value))
-}
+    /// Sorts the sequences by length in the given order, keeping the order
+    /// of sequences of equal length.
+    pub fn sort_by_length(&mut self, order: SortOrder, placement: RepresentativePlacement) {
+        match order {
+            SortOrder::Ascending => self.sort_by_key(|s| s.length, placement),
+            SortOrder::Descending => self.sort_by_key(|s| std::cmp::Reverse(s.length), placement),
+        }
+    }
 
-/// Function to parse a `.clstr` file from a path.
-pub fn from_path<P: AsRef<Path>>(path: P) -> Result<ClstrParser<BufReader<File>>> {
-    let file = File::open(path).map_err(Error::from)?;
-    let reader = BufReader::new(file);
-    Ok(ClstrParser::new(reader))
-}
+    /// Sorts the sequences longest first.
+    pub fn sort_by_length_desc(&mut self, placement: RepresentativePlacement) {
+        self.sort_by_length(SortOrder::Descending, placement);
+    }
 
-/// Function to parse a `.clstr` file from a reader.
-pub fn from_reader<R: BufRead>(reader: R) -> ClstrParser<R> {
-    ClstrParser::new(reader)
+    /// Sorts the sequences by identity to the representative, highest first,
+    /// with those that have no identity last.
+    pub fn sort_by_identity_desc(&mut self, placement: RepresentativePlacement) {
+        self.sort_by(
+            |a, b| match (a.identity, b.identity) {
+                (Some(a), Some(b)) => b.total_cmp(&a),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            },
+            placement,
+        );
+    }
 }
 
-/// Struct to write `.clstr` format files.
-pub struct ClstrWriter<W: Write> {
-    writer: W,
+// sound as long as the sequences are, see above
+impl Eq for Cluster {}
+
+/// Clusters order by size, smallest first, so that sorting puts the largest
+/// last and a [`std::collections::BinaryHeap`] pops the largest first. They
+/// do not order by ID: clusters of one size order by cluster ID and then by
+/// their contents, only to agree with equality.
+impl Ord for Cluster {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.size()
+            .cmp(&other.size())
+            .then_with(|| self.cluster_id.cmp(&other.cluster_id))
+            .then_with(|| self.header_note.cmp(&other.header_note))
+            .then_with(|| {
+                let key = |s: &Sequence| {
+                    (
+                        s.length,
+                        s.unit,
+                        // identities are never NaN, so with -0.0 made 0.0 their
+                        // bits are equal exactly when they are
+                        s.identity.map(|i| (i + 0.0).to_bits()),
+                        s.strand,
+                        s.is_representative,
+                        s.maybe_truncated,
+                    )
+                };
+                self.sequences
+                    .iter()
+                    .map(|s| (&s.id, key(s)))
+                    .cmp(other.sequences.iter().map(|s| (&s.id, key(s))))
+            })
+    }
 }
 
-impl<W: Write> ClstrWriter<W> {
-    /// Creates a new `ClstrWriter`.
-    pub fn new(writer: W) -> Self {
-        ClstrWriter { writer }
+impl PartialOrd for Cluster {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
     }
+}
 
-    /// Writes a cluster to the `.clstr` format.
-    pub fn write_cluster(&mut self, cluster: &Cluster) -> Result<()> {
-        // Write the cluster header: >Cluster <ID>
-        writeln!(self.writer, ">Cluster {}", cluster.cluster_id())?;
+/// Parses a single cluster, its header and member lines, with the default
+/// [`ParseOptions`]. Errors are those of [`ClstrParser`], and more than one
+/// cluster is an error too.
+impl std::str::FromStr for Cluster {
+    type Err = Error;
 
-        // Write each sequence in the cluster.
-        for (index, seq) in cluster.sequences().iter().enumerate() {
-            self.write_sequence(index, seq)?;
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = crate::from_str(s);
+        let cluster = parser.next().unwrap_or_else(|| {
+            Err(Error::new(ErrorKind::ReadRecord(
+                "no cluster to parse".to_string(),
+            )))
+        })?;
+
+        // the parser has read the header of any next cluster by now
+        let line = parser.line_number();
+        match parser.next() {
+            None => Ok(cluster),
+            Some(Err(e)) => Err(e),
+            Some(Ok(_)) => Err(parse_error(
+                line,
+                None,
+                "expected a single cluster, found another".to_string(),
+            )),
         }
+    }
+}
 
+/// Formats the cluster as [`ClstrWriter`] writes it by default: the header
+/// and numbered member lines, each ending in a line break.
+impl std::fmt::Display for Cluster {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, ">Cluster {}", self.cluster_id)?;
+        for (index, seq) in self.sequences.iter().enumerate() {
+            writeln!(f, "{index}    {seq}")?;
+        }
         Ok(())
     }
+}
 
-    /// Writes a single sequence to the `.clstr` format.
-    fn write_sequence(&mut self, index: usize, sequence: &Sequence) -> Result<()> {
-        let unit = match sequence.unit {
-            LengthUnit::Aa => "aa",
-            LengthUnit::Nt => "nt",
-        };
-
-        write!(
-            self.writer,
-            "{}    {}{}, >{}...",
-            index,
-            sequence.length(),
-            unit,
-            sequence.id()
-        )?;
-
-        if let Some(identity) = sequence.identity() {
-            write!(self.writer, " at {identity:.2}%")?;
+impl Hash for Cluster {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.cluster_id.hash(state);
+        for seq in &self.sequences {
+            seq.id.hash(state);
         }
+    }
+}
 
-        if sequence.is_representative() {
-            write!(self.writer, " *")?;
-        }
+impl<'a> IntoIterator for &'a Cluster {
+    type Item = &'a Sequence;
+    type IntoIter = std::slice::Iter<'a, Sequence>;
 
-        writeln!(self.writer)?;
+    fn into_iter(self) -> Self::IntoIter {
+        self.sequences.iter()
+    }
+}
 
-        Ok(())
+impl<'a> IntoIterator for &'a mut Cluster {
+    type Item = &'a mut Sequence;
+    type IntoIter = std::slice::IterMut<'a, Sequence>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.sequences_mut().iter_mut()
     }
+}
 
-    /// Finalize the writer by flushing any remaining output.
-    pub fn flush(&mut self) -> io::Result<()> {
-        self.writer.flush()
+impl IntoIterator for Cluster {
+    type Item = Sequence;
+    type IntoIter = std::vec::IntoIter<Sequence>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.sequences.into_iter()
     }
 }
 
-/// Helper function to create a writer from a file path.
-pub fn to_path<P: AsRef<Path>>(path: P) -> Result<ClstrWriter<File>> {
-    let file = File::create(path)?;
-    Ok(ClstrWriter::new(file))
+impl Extend<Sequence> for Cluster {
+    fn extend<I: IntoIterator<Item = Sequence>>(&mut self, iter: I) {
+        self.sequences_mut().extend(iter);
+    }
 }
 
-#[cfg(test)]
+/// Collects sequences into a cluster with ID 0.
+impl FromIterator<Sequence> for Cluster {
+    fn from_iter<I: IntoIterator<Item = Sequence>>(iter: I) -> Self {
+        Cluster {
+            cluster_id: 0,
+            sequences: iter.into_iter().collect(),
+            header_note: None,
+            id_index: IdIndex::default(),
+        }
+    }
+}
+
+/// The kind of sequences a `.clstr` file is expected to contain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SequenceType {
+    /// Accept both amino acid and nucleotide sequences.
+    #[default]
+    Auto,
+    /// Only accept amino acid (`aa`) sequences.
+    Amino,
+    /// Only accept nucleotide (`nt`) sequences.
+    Nucleotide,
+}
+
+/// What to do when a cluster ID from a header has already been seen, as in
+/// several `.clstr` files concatenated together.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateIdPolicy {
+    /// Keep the repeated IDs as they are.
+    #[default]
+    Keep,
+    /// Fail on the first repeated ID.
+    Error,
+    /// Number all clusters sequentially from 0 in file order, as
+    /// [`renumber_clusters`] does.
+    Renumber,
+}
+
+/// What to do with a line that is not valid UTF-8, as when a FASTA header
+/// with Latin-1 bytes was copied into the `.clstr` file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvalidUtf8Policy {
+    /// Fail with a parse error.
+    #[default]
+    Error,
+    /// Replace the invalid bytes with U+FFFD, the replacement character.
+    Lossy,
+    /// Skip the line, reporting it to [`ParseOptions::on_warning`].
+    Skip,
+}
+
+/// What to do when the input ends partway through a line, as when CD-HIT was
+/// killed mid-write or a copy was interrupted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TruncationPolicy {
+    /// Fail with a parse error.
+    Error,
+    /// Keep the last cluster as it is, reporting the last line to
+    /// [`ParseOptions::on_warning`].
+    #[default]
+    WarnAndKeep,
+    /// Drop the last cluster, which may be missing members. If the cut line
+    /// is a header, only the header is dropped, as the cluster before it is
+    /// complete. Iterating with [`ClstrParser::into_sequences`] only drops
+    /// the last line.
+    Drop,
+}
+
+/// What to do with a header without a parseable cluster number, such as a
+/// bare `>Cluster`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BadHeaderPolicy {
+    /// Fail with an error.
+    #[default]
+    Error,
+    /// Give the cluster the ID after the previous cluster's, or 0 for the
+    /// first, reporting the header to [`ParseOptions::on_warning`].
+    AssignSequential,
+    /// Skip the header and the sequences under it, reporting the header to
+    /// [`ParseOptions::on_warning`].
+    Skip,
+}
+
+/// A line that was skipped or kept despite a problem, as reported to
+/// [`ParseOptions::on_warning`] and kept by [`ClstrParser::warnings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    /// The 1-based line number.
+    pub line: u64,
+    /// The text of the line, without its terminator.
+    pub text: String,
+    /// What was wrong with the line.
+    pub msg: String,
+}
+
+impl std::fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {} in '{}'", self.line, self.msg, self.text)
+    }
+}
+
+/// A callback receiving a skipped line and a description of what was wrong
+/// with it.
+pub type WarningCallback = Box<dyn Fn(&str, &str) + Send + Sync>;
+
+/// Options controlling how a [`ClstrParser`] reads its input.
+pub struct ParseOptions {
+    /// The kind of sequences expected; sequences of another kind are
+    /// malformed.
+    pub sequence_type: SequenceType,
+    /// Fail on malformed sequence lines if `true`, otherwise skip them.
+    pub strict: bool,
+    /// Take cluster IDs from the `>Cluster N` headers if `true`, otherwise
+    /// number clusters from 0 in the order they appear.
+    pub preserve_cluster_ids: bool,
+    /// The largest identity accepted on a sequence line.
+    pub max_identity_value: f32,
+    /// The smallest identity accepted on a sequence line. Identities that
+    /// are not finite are never accepted.
+    pub min_identity_value: f32,
+    /// Called for each malformed line skipped when `strict` is `false`. The
+    /// same warnings are kept by [`ClstrParser::warnings`].
+    pub on_warning: Option<WarningCallback>,
+    /// What to do with repeated cluster IDs when `preserve_cluster_ids` is
+    /// `true`.
+    pub on_duplicate_id: DuplicateIdPolicy,
+    /// Read the `+/` or `-/` strand prefix CD-HIT-EST writes before the
+    /// identity into [`Sequence::strand`].
+    pub cd_hit_est: bool,
+    /// What to do with lines that are not valid UTF-8.
+    pub on_invalid_utf8: InvalidUtf8Policy,
+    /// What to do when the last line has no trailing newline.
+    pub on_truncation: TruncationPolicy,
+    /// The description length CD-HIT was run with, its `-d` option, 20 by
+    /// default. CD-HIT cuts sequence names to one character less, so IDs at
+    /// least `description_length - 1` long are flagged by
+    /// [`Sequence::maybe_truncated`]; 0, as with `-d 0`, never flags them.
+    pub description_length: usize,
+    /// What to do with headers without a parseable cluster number when
+    /// `preserve_cluster_ids` is `true`. When `strict` is `false` the
+    /// `>Cluster` prefix is also matched case-insensitively.
+    pub on_bad_header: BadHeaderPolicy,
+    /// Check that the index starting each sequence line matches its
+    /// position in the cluster, failing if not when `strict` is `true` and
+    /// otherwise reporting the line to `on_warning` and keeping it.
+    pub check_indices: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            sequence_type: SequenceType::Auto,
+            strict: true,
+            preserve_cluster_ids: true,
+            max_identity_value: 100.0,
+            min_identity_value: 0.0,
+            on_warning: None,
+            on_duplicate_id: DuplicateIdPolicy::Keep,
+            cd_hit_est: false,
+            on_invalid_utf8: InvalidUtf8Policy::Error,
+            on_truncation: TruncationPolicy::WarnAndKeep,
+            // CD-HIT's default of `-d 20` keeps 19 characters
+            description_length: 20,
+            on_bad_header: BadHeaderPolicy::Error,
+            check_indices: false,
+        }
+    }
+}
+
+impl std::fmt::Debug for ParseOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ParseOptions")
+            .field("sequence_type", &self.sequence_type)
+            .field("strict", &self.strict)
+            .field("preserve_cluster_ids", &self.preserve_cluster_ids)
+            .field("max_identity_value", &self.max_identity_value)
+            .field("min_identity_value", &self.min_identity_value)
+            .field("on_warning", &self.on_warning.as_ref().map(|_| ".."))
+            .field("on_duplicate_id", &self.on_duplicate_id)
+            .field("cd_hit_est", &self.cd_hit_est)
+            .field("on_invalid_utf8", &self.on_invalid_utf8)
+            .field("on_truncation", &self.on_truncation)
+            .field("description_length", &self.description_length)
+            .field("on_bad_header", &self.on_bad_header)
+            .field("check_indices", &self.check_indices)
+            .finish()
+    }
+}
+
+impl ParseOptions {
+    /// Check a parsed sequence against these options.
+    fn check_sequence(&self, seq: &Sequence, line_no: u64) -> Result<()> {
+        match (self.sequence_type, seq.unit) {
+            (SequenceType::Amino, LengthUnit::Nt) => {
+                return Err(parse_error(
+                    line_no,
+                    None,
+                    "expected an amino acid sequence, found nucleotides".to_string(),
+                ))
+            }
+            (SequenceType::Nucleotide, LengthUnit::Aa) => {
+                return Err(parse_error(
+                    line_no,
+                    None,
+                    "expected a nucleotide sequence, found amino acids".to_string(),
+                ))
+            }
+            _ => {}
+        }
+
+        if let Some(identity) = seq.identity {
+            let msg = if !identity.is_finite() {
+                format!("identity {identity}% is not a finite number")
+            } else if identity > self.max_identity_value {
+                format!(
+                    "identity {identity}% exceeds the maximum of {}%",
+                    self.max_identity_value
+                )
+            } else if identity < self.min_identity_value {
+                format!(
+                    "identity {identity}% is below the minimum of {}%",
+                    self.min_identity_value
+                )
+            } else {
+                return Ok(());
+            };
+            return Err(parse_error(line_no, None, msg));
+        }
+
+        Ok(())
+    }
+}
+
+/// The line-by-line state of parsing a `.clstr` file, independent of how the
+/// lines are read.
+struct LineState {
+    /// The 1-based number of the last line read, 0 before any input.
+    line: u64,
+    /// The byte offset of the start of the last line read.
+    offset: u64,
+    /// The byte offset of the start of the next line to be read.
+    next_offset: u64,
+    /// The options controlling parsing.
+    options: ParseOptions,
+    /// The ID given to the next cluster when not preserving cluster IDs.
+    next_cluster_id: usize,
+    /// The ID of the cluster whose sequences are being read, or `None` when
+    /// they are being skipped.
+    cluster_id: Option<usize>,
+    /// The ID given to the last cluster.
+    last_cluster_id: Option<usize>,
+    /// The position in its cluster of the next sequence.
+    position: usize,
+    /// The cluster IDs seen so far, only kept to reject duplicates.
+    seen_ids: HashSet<usize>,
+    /// The warnings raised so far.
+    warnings: Vec<ParseWarning>,
+    /// Whether the last line read had no newline.
+    truncated: bool,
+}
+
+impl LineState {
+    fn new(options: ParseOptions) -> Self {
+        LineState {
+            line: 0,
+            offset: 0,
+            next_offset: 0,
+            options,
+            next_cluster_id: 0,
+            cluster_id: None,
+            last_cluster_id: None,
+            position: 0,
+            seen_ids: HashSet::new(),
+            warnings: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    /// Wraps an error reading the input with the byte offset of the line
+    /// being read.
+    fn read_error(&self, err: io::Error) -> Error {
+        let msg = format!("{err} (at byte offset {})", self.next_offset);
+        Error::from(io::Error::new(err.kind(), msg))
+    }
+
+    /// Reports a problem with the current line to the warning callback and
+    /// records it.
+    fn warn(&mut self, text: &str, msg: &str) {
+        if let Some(ref on_warning) = self.options.on_warning {
+            on_warning(text, msg);
+        }
+        self.warnings.push(ParseWarning {
+            line: self.line,
+            text: text.to_string(),
+            msg: msg.to_string(),
+        });
+    }
+
+    /// Parses the next line of input, terminator included. Returns `None` for
+    /// lines that are skipped: malformed sequence lines in lenient mode,
+    /// invalid UTF-8 under [`InvalidUtf8Policy::Skip`], and anything before
+    /// the first header.
+    fn parse_line(&mut self, raw: &[u8]) -> Option<Result<Record>> {
+        self.line += 1;
+        self.offset = self.next_offset;
+        self.next_offset += raw.len() as u64;
+
+        // only the last line of the input can lack a newline
+        if !raw.ends_with(b"\n") {
+            self.truncated = true;
+            const TRUNCATED: &str = "file is truncated, the last line has no newline";
+            match self.options.on_truncation {
+                TruncationPolicy::Error => {
+                    return Some(Err(parse_error(self.line, None, TRUNCATED.to_string())))
+                }
+                TruncationPolicy::WarnAndKeep => {
+                    self.warn(String::from_utf8_lossy(raw).trim_ascii_end(), TRUNCATED)
+                }
+                // a cut header has no members yet, so the cluster before
+                // it is whole
+                TruncationPolicy::Drop if raw.trim_ascii_start().starts_with(b">") => return None,
+                TruncationPolicy::Drop => return Some(Ok(Record::Truncated)),
+            }
+        }
+
+        let raw = match std::str::from_utf8(raw) {
+            Ok(raw) => Cow::Borrowed(raw),
+            Err(e) => match self.options.on_invalid_utf8 {
+                InvalidUtf8Policy::Error => {
+                    return Some(Err(parse_error(
+                        self.line,
+                        Some(e.valid_up_to() + 1),
+                        "invalid UTF-8".to_string(),
+                    )))
+                }
+                InvalidUtf8Policy::Lossy => String::from_utf8_lossy(raw),
+                InvalidUtf8Policy::Skip => {
+                    self.warn(
+                        String::from_utf8_lossy(raw).trim_ascii_end(),
+                        "invalid UTF-8",
+                    );
+                    return None;
+                }
+            },
+        };
+
+        // strip the line terminator, whether LF or CRLF, along with any
+        // trailing whitespace so that the final token is left intact
+        let line = raw.trim_ascii_end();
+
+        if line.starts_with('>') {
+            let renumber = !self.options.preserve_cluster_ids
+                || self.options.on_duplicate_id == DuplicateIdPolicy::Renumber;
+            let ignore_case = !self.options.strict;
+
+            let cluster_id = if renumber {
+                self.next_cluster_id
+            } else {
+                match parse_cluster_id(line, ignore_case) {
+                    Some(id) => id,
+                    None => match self.options.on_bad_header {
+                        BadHeaderPolicy::Error => {
+                            return Some(Err(parse_error(
+                                self.line,
+                                None,
+                                format!("invalid cluster header '{line}'"),
+                            )))
+                        }
+                        BadHeaderPolicy::AssignSequential => {
+                            let id = self.last_cluster_id.map_or(0, |id| id + 1);
+                            self.warn(line, &format!("no cluster number, assigned {id}"));
+                            id
+                        }
+                        BadHeaderPolicy::Skip => {
+                            self.warn(line, "no cluster number, skipping the cluster");
+                            self.cluster_id = None;
+                            return None;
+                        }
+                    },
+                }
+            };
+
+            if self.options.on_duplicate_id == DuplicateIdPolicy::Error
+                && !renumber
+                && !self.seen_ids.insert(cluster_id)
+            {
+                return Some(Err(parse_error(
+                    self.line,
+                    None,
+                    format!("duplicate cluster ID {cluster_id}"),
+                )));
+            }
+            self.next_cluster_id += 1;
+            self.cluster_id = Some(cluster_id);
+            self.last_cluster_id = Some(cluster_id);
+            self.position = 0;
+
+            let note = split_header(line, ignore_case)
+                .and_then(|(_, note)| note)
+                .map(String::from);
+            return Some(Ok(Record::Header(cluster_id, note)));
+        }
+
+        let cluster_id = self.cluster_id?;
+        let parsed =
+            parse_sequence_line(line, self.line, &self.options).and_then(|(index, seq)| {
+                self.options.check_sequence(&seq, self.line)?;
+                Ok((index, seq))
+            });
+
+        match parsed {
+            Ok((index, seq)) => {
+                let position = self.position;
+                self.position += 1;
+
+                if self.options.check_indices && index != position {
+                    let msg = format!("index {index} does not match position {position}");
+                    if self.options.strict {
+                        let column = column_of(line, line.trim_start());
+                        return Some(Err(parse_error(self.line, Some(column), msg)));
+                    }
+                    self.warn(line, &msg);
+                }
+
+                Some(Ok(Record::Sequence(cluster_id, index, seq)))
+            }
+            Err(e) if !self.options.strict => {
+                self.warn(line, &e.to_string());
+                None
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Adds a record to the cluster being gathered in `current`, returning the
+/// previous cluster once a header shows it is complete.
+fn gather_record(current: &mut Option<Cluster>, record: Record) -> Option<Cluster> {
+    match record {
+        Record::Header(cluster_id, header_note) => {
+            let next_cluster = Some(Cluster {
+                cluster_id,
+                sequences: Vec::new(),
+                header_note,
+                id_index: IdIndex::default(),
+            });
+            std::mem::replace(current, next_cluster)
+        }
+        Record::Sequence(_, _, seq) => {
+            if let Some(c) = current {
+                c.sequences.push(seq);
+            }
+            None
+        }
+        Record::Truncated => {
+            *current = None;
+            None
+        }
+    }
+}
+
+/// Numbers clusters sequentially from 0 in the order they are given.
+pub fn renumber_clusters<I: IntoIterator<Item = Cluster>>(
+    clusters: I,
+) -> impl Iterator<Item = Cluster> {
+    clusters
+        .into_iter()
+        .enumerate()
+        .map(|(cluster_id, mut cluster)| {
+            cluster.cluster_id = cluster_id;
+            cluster
+        })
+}
+
+/// Numbers clusters sequentially from 0 in the order they are given, as
+/// [`renumber_clusters`] does, passing errors through without using up a
+/// number.
+pub fn renumber<I: IntoIterator<Item = Result<Cluster>>>(
+    clusters: I,
+) -> impl Iterator<Item = Result<Cluster>> {
+    let mut next_id = 0;
+    clusters.into_iter().map(move |cluster| {
+        cluster.map(|mut c| {
+            c.cluster_id = next_id;
+            next_id += 1;
+            c
+        })
+    })
+}
+
+/// Renumbers clusters as [`renumber`] does, pushing each old ID and the new
+/// ID it was given onto `mapping`.
+pub fn renumber_with_mapping<'a, I>(
+    clusters: I,
+    mapping: &'a mut Vec<(usize, usize)>,
+) -> impl Iterator<Item = Result<Cluster>> + 'a
+where
+    I: IntoIterator<Item = Result<Cluster>>,
+    I::IntoIter: 'a,
+{
+    clusters.into_iter().map(move |cluster| {
+        cluster.map(|mut c| {
+            let new_id = mapping.len();
+            mapping.push((c.cluster_id, new_id));
+            c.cluster_id = new_id;
+            c
+        })
+    })
+}
+
+/// A cluster held by [`top_n_by`], ordered so that the cluster to give up
+/// first is the greatest.
+struct Ranked<K> {
+    /// The key, then the cluster ID and position in the input, lowest first.
+    rank: (std::cmp::Reverse<K>, usize, usize),
+    cluster: Cluster,
+}
+
+impl<K: Ord> PartialEq for Ranked<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.rank == other.rank
+    }
+}
+
+impl<K: Ord> Eq for Ranked<K> {}
+
+impl<K: Ord> PartialOrd for Ranked<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord> Ord for Ranked<K> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.rank.cmp(&other.rank)
+    }
+}
+
+/// Returns the `n` clusters with the greatest `key`, greatest first, with
+/// clusters of equal key in order of cluster ID and then of input. Only `n`
+/// clusters are held at a time, so memory does not grow with the input.
+/// Fails at the first error.
+pub fn top_n_by<I, K, F>(clusters: I, n: usize, mut key: F) -> Result<Vec<Cluster>>
+where
+    I: IntoIterator<Item = Result<Cluster>>,
+    K: Ord,
+    F: FnMut(&Cluster) -> K,
+{
+    // a max-heap of the clusters most easily given up, the worst on top
+    let mut heap: BinaryHeap<Ranked<K>> = BinaryHeap::new();
+    for (position, cluster) in clusters.into_iter().enumerate() {
+        let cluster = cluster?;
+        let ranked = Ranked {
+            rank: (
+                std::cmp::Reverse(key(&cluster)),
+                cluster.cluster_id,
+                position,
+            ),
+            cluster,
+        };
+
+        if heap.len() < n {
+            heap.push(ranked);
+        } else if let Some(mut worst) = heap.peek_mut() {
+            if ranked < *worst {
+                *worst = ranked;
+            }
+        }
+    }
+
+    Ok(heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|r| r.cluster)
+        .collect())
+}
+
+/// An edge from a representative to another sequence of its cluster, as
+/// returned by [`Cluster::to_edges`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edge<'a> {
+    /// The ID of the representative.
+    pub rep_id: &'a str,
+    /// The ID of the other sequence.
+    pub member_id: &'a str,
+    /// The identity of the other sequence to the representative, if
+    /// available.
+    pub identity: Option<f32>,
+}
+
+/// What [`representatives_with`] and [`write_edge_list`] do with a cluster
+/// that has no representative, as when the `*` line was edited out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingRepresentative {
+    /// Use the longest sequence, as [`Cluster::representative_or_longest`]
+    /// picks it.
+    #[default]
+    Longest,
+    /// Skip the cluster, counting it in [`Representatives::skipped`] or the
+    /// count returned by [`write_edge_list`].
+    Skip,
+}
+
+/// Iterator over the cluster ID and representative of each cluster, returned
+/// by [`representatives`] and [`representatives_with`].
+pub struct Representatives<I> {
+    /// The clusters.
+    clusters: I,
+    /// What to do with a cluster without a representative.
+    on_missing: MissingRepresentative,
+    /// The number of clusters nothing was yielded for.
+    skipped: u64,
+}
+
+impl<I> Representatives<I> {
+    /// Returns the number of clusters skipped so far, for being empty or, with
+    /// [`MissingRepresentative::Skip`], for having no representative.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+impl<I: Iterator<Item = Result<Cluster>>> Iterator for Representatives<I> {
+    type Item = Result<(usize, Sequence)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut cluster = match self.clusters.next()? {
+                Ok(cluster) => cluster,
+                Err(e) => return Some(Err(e)),
+            };
+            let chosen = match self.on_missing {
+                MissingRepresentative::Longest => cluster.representative_or_longest(),
+                MissingRepresentative::Skip => cluster.get_representative(),
+            };
+            let position = chosen.and_then(|chosen| {
+                cluster
+                    .sequences
+                    .iter()
+                    .position(|s| std::ptr::eq(s, chosen))
+            });
+
+            match position {
+                Some(position) => {
+                    let seq = cluster.sequences.swap_remove(position);
+                    return Some(Ok((cluster.cluster_id, seq)));
+                }
+                None => self.skipped += 1,
+            }
+        }
+    }
+}
+
+/// Returns an iterator over the cluster ID and representative of each of
+/// `clusters`, falling back to the longest sequence for a cluster without a
+/// representative. Empty clusters are skipped. One cluster is held at a time.
+pub fn representatives<I: IntoIterator<Item = Result<Cluster>>>(
+    clusters: I,
+) -> Representatives<I::IntoIter> {
+    representatives_with(clusters, MissingRepresentative::default())
+}
+
+/// Returns an iterator over the cluster ID and representative of each of
+/// `clusters`, as [`representatives`] does, handling clusters without a
+/// representative as `on_missing` says.
+pub fn representatives_with<I: IntoIterator<Item = Result<Cluster>>>(
+    clusters: I,
+    on_missing: MissingRepresentative,
+) -> Representatives<I::IntoIter> {
+    Representatives {
+        clusters: clusters.into_iter(),
+        on_missing,
+        skipped: 0,
+    }
+}
+
+/// Iterator to parse `.clstr` file.
+pub struct ClstrParser<R: BufRead> {
+    /// The reader to parse the file.
+    reader: R,
+    /// The current cluster being parsed.
+    current_cluster: Option<Cluster>,
+    /// Buffer holding the line currently being parsed.
+    buf: Vec<u8>,
+    /// The state of parsing.
+    state: LineState,
+    /// The length in bytes of the input, if known, for
+    /// [`ClstrParser::estimated_clusters`].
+    input_len: Option<u64>,
+}
+
+/// A guess at the length in bytes of a cluster, for estimating the number
+/// of clusters in an input.
+const AVG_BYTES_PER_CLUSTER: u64 = 200;
+
+impl<R: BufRead> ClstrParser<R> {
+    /// Creates a parser with the default [`ParseOptions`].
+    pub fn new(reader: R) -> Self {
+        ClstrParser::with_options(reader, ParseOptions::default())
+    }
+
+    /// Creates a parser with the given options.
+    pub fn with_options(reader: R, options: ParseOptions) -> Self {
+        ClstrParser {
+            reader,
+            current_cluster: None,
+            buf: Vec::new(),
+            state: LineState::new(options),
+            input_len: None,
+        }
+    }
+
+    /// Sets the length in bytes of the input, from which
+    /// [`ClstrParser::estimated_clusters`] guesses the number of clusters
+    /// left. [`from_path`] sets this for uncompressed files.
+    pub fn with_input_len(mut self, len: u64) -> Self {
+        self.input_len = Some(len);
+        self
+    }
+
+    /// Returns a guess at the number of clusters left, from the bytes of
+    /// input not yet read, or `None` if the length of the input is unknown.
+    /// The guess is for sizing buffers and may be far off either way, so
+    /// [`Iterator::size_hint`] does not report it.
+    pub fn estimated_clusters(&self) -> Option<usize> {
+        self.input_len.map(|len| {
+            (len.saturating_sub(self.state.next_offset) / AVG_BYTES_PER_CLUSTER) as usize
+        })
+    }
+
+    /// Counts lines and byte offsets on from `line` lines and `offset` bytes
+    /// already read, for a parser over part of a larger input.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn starting_at(mut self, line: u64, offset: u64) -> Self {
+        self.state.line = line;
+        self.state.offset = offset;
+        self.state.next_offset = offset;
+        self
+    }
+
+    /// Returns the options this parser was created with.
+    pub fn options(&self) -> &ParseOptions {
+        &self.state.options
+    }
+
+    /// Returns the warnings raised so far, one for each malformed line
+    /// skipped when [`ParseOptions::strict`] is `false` and for any other
+    /// line reported to [`ParseOptions::on_warning`].
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.state.warnings
+    }
+
+    /// Returns whether the last line read had no newline, as in a file cut
+    /// off while it was being written. This is only known once the end of
+    /// the input has been reached.
+    pub fn is_truncated(&self) -> bool {
+        self.state.truncated
+    }
+
+    /// Returns the 1-based number of the last line read, or 0 if nothing has
+    /// been read yet.
+    pub fn line_number(&self) -> u64 {
+        self.state.line
+    }
+
+    /// Returns the byte offset of the start of the last line read.
+    pub fn byte_offset(&self) -> u64 {
+        self.state.offset
+    }
+
+    /// Lazily keeps only the clusters for which `predicate` returns `true`.
+    /// Errors are always passed through.
+    pub fn filter_clusters<F: Fn(&Cluster) -> bool>(
+        self,
+        predicate: F,
+    ) -> FilteredClstrParser<R, F> {
+        FilteredClstrParser {
+            parser: self,
+            predicate,
+        }
+    }
+
+    /// Lazily applies `f` to each cluster. Errors are passed through.
+    pub fn map_clusters<B, F: Fn(Cluster) -> B>(self, f: F) -> MappedClstrParser<R, F> {
+        MappedClstrParser { parser: self, f }
+    }
+
+    /// Calls `callback` with the number of clusters parsed so far after
+    /// every 10,000 clusters, or as often as
+    /// [`ProgressClstrParser::with_progress_interval`] sets.
+    pub fn with_progress<F: Fn(u64)>(self, callback: F) -> ProgressClstrParser<R, F> {
+        ProgressClstrParser {
+            parser: self,
+            callback,
+            interval: DEFAULT_PROGRESS_INTERVAL,
+            count: 0,
+        }
+    }
+}
+
+/// The number of clusters between calls to a progress callback, unless set.
+const DEFAULT_PROGRESS_INTERVAL: u64 = 10_000;
+
+/// Iterator returned by [`ClstrParser::filter_clusters`].
+pub struct FilteredClstrParser<R: BufRead, F> {
+    parser: ClstrParser<R>,
+    predicate: F,
+}
+
+impl<R: BufRead, F: Fn(&Cluster) -> bool> Iterator for FilteredClstrParser<R, F> {
+    type Item = Result<Cluster>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for cluster in self.parser.by_ref() {
+            match cluster {
+                Ok(c) if !(self.predicate)(&c) => continue,
+                other => return Some(other),
+            }
+        }
+        None
+    }
+}
+
+/// Iterator returned by [`ClstrParser::map_clusters`].
+pub struct MappedClstrParser<R: BufRead, F> {
+    parser: ClstrParser<R>,
+    f: F,
+}
+
+impl<R: BufRead, B, F: Fn(Cluster) -> B> Iterator for MappedClstrParser<R, F> {
+    type Item = Result<B>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.parser.next().map(|cluster| cluster.map(&self.f))
+    }
+}
+
+/// Iterator returned by [`ClstrParser::with_progress`].
+pub struct ProgressClstrParser<R: BufRead, F> {
+    parser: ClstrParser<R>,
+    callback: F,
+    /// The number of clusters between calls to `callback`.
+    interval: u64,
+    /// The number of clusters parsed so far.
+    count: u64,
+}
+
+impl<R: BufRead, F: Fn(u64)> ProgressClstrParser<R, F> {
+    /// Sets the number of clusters between calls to the callback. An
+    /// interval of 0 is taken as 1.
+    pub fn with_progress_interval(mut self, interval: u64) -> Self {
+        self.interval = interval.max(1);
+        self
+    }
+}
+
+impl<R: BufRead, F: Fn(u64)> Iterator for ProgressClstrParser<R, F> {
+    type Item = Result<Cluster>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cluster = self.parser.next()?;
+        if cluster.is_ok() {
+            self.count += 1;
+            if self.count.is_multiple_of(self.interval) {
+                (self.callback)(self.count);
+            }
+        }
+        Some(cluster)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.parser.size_hint()
+    }
+}
+
+/// Filters for any iterator of parsed clusters, such as a [`ClstrParser`].
+/// Each keeps the clusters that pass and passes errors through untouched,
+/// except the partitions, which stop at the first error.
+pub trait ClusterIteratorExt: Iterator<Item = Result<Cluster>> + Sized {
+    /// Keeps the clusters for which `predicate` returns `true`.
+    fn filter_ok<F: FnMut(&Cluster) -> bool>(
+        self,
+        mut predicate: F,
+    ) -> impl Iterator<Item = Result<Cluster>> {
+        self.filter(move |cluster| cluster.as_ref().map_or(true, &mut predicate))
+    }
+
+    /// Keeps the clusters with at least `n` sequences.
+    fn min_size(self, n: usize) -> impl Iterator<Item = Result<Cluster>> {
+        self.filter_ok(move |c| c.size() >= n)
+    }
+
+    /// Keeps the clusters with at most `n` sequences.
+    fn max_size(self, n: usize) -> impl Iterator<Item = Result<Cluster>> {
+        self.filter_ok(move |c| c.size() <= n)
+    }
+
+    /// Keeps the clusters with exactly one sequence.
+    fn singletons(self) -> impl Iterator<Item = Result<Cluster>> {
+        self.filter_ok(Cluster::is_singleton)
+    }
+
+    /// Keeps the clusters with other than one sequence.
+    fn non_singletons(self) -> impl Iterator<Item = Result<Cluster>> {
+        self.filter_ok(|c| !c.is_singleton())
+    }
+
+    /// Keeps the clusters with at least `min` and, if given, at most `max`
+    /// sequences. Nothing is kept if `min` is more than `max`.
+    fn filter_size_range(
+        self,
+        min: usize,
+        max: Option<usize>,
+    ) -> impl Iterator<Item = Result<Cluster>> {
+        self.filter_ok(move |c| c.size() >= min && max.is_none_or(|max| c.size() <= max))
+    }
+
+    /// Keeps the clusters whose representative is at least `length` long.
+    /// Clusters without a representative are dropped.
+    fn min_rep_length(self, length: u32) -> impl Iterator<Item = Result<Cluster>> {
+        self.filter_ok(move |c| c.representative_length().is_some_and(|l| l >= length))
+    }
+
+    /// Keeps the clusters with at least one sequence whose ID is in `ids`.
+    fn ids_in<'a>(self, ids: &'a HashSet<&str>) -> impl Iterator<Item = Result<Cluster>> + 'a
+    where
+        Self: 'a,
+    {
+        self.filter_ok(move |c| c.iter().any(|s| ids.contains(s.id())))
+    }
+
+    /// Splits the clusters into those for which `f` returns `true` and the
+    /// rest, each in order.
+    fn partition_by<F: FnMut(&Cluster) -> bool>(
+        self,
+        mut f: F,
+    ) -> Result<(Vec<Cluster>, Vec<Cluster>)> {
+        let mut matching = Vec::new();
+        let mut rest = Vec::new();
+        for cluster in self {
+            let cluster = cluster?;
+            if f(&cluster) {
+                matching.push(cluster);
+            } else {
+                rest.push(cluster);
+            }
+        }
+        Ok((matching, rest))
+    }
+
+    /// Writes the clusters for which `f` returns `true` to `matching` and
+    /// the rest to `rest` as they are read, then flushes both, as a streaming
+    /// [`ClusterIteratorExt::partition_by`].
+    fn partition_write<F, A, B>(
+        self,
+        mut f: F,
+        matching: &mut ClstrWriter<A>,
+        rest: &mut ClstrWriter<B>,
+    ) -> Result<()>
+    where
+        F: FnMut(&Cluster) -> bool,
+        A: Write,
+        B: Write,
+    {
+        for cluster in self {
+            let cluster = cluster?;
+            if f(&cluster) {
+                matching.write_cluster(&cluster)?;
+            } else {
+                rest.write_cluster(&cluster)?;
+            }
+        }
+        matching.flush()?;
+        rest.flush()?;
+        Ok(())
+    }
+}
+
+impl<I: Iterator<Item = Result<Cluster>>> ClusterIteratorExt for I {}
+
+/// A single meaningful line of a `.clstr` file.
+enum Record {
+    /// A `>Cluster` header, with the ID given to the cluster and any note
+    /// after the number.
+    Header(usize, Option<String>),
+    /// A sequence line, with the ID of the cluster it belongs to and the
+    /// index from its first column.
+    Sequence(usize, usize, Sequence),
+    /// The last line, when it was cut short and the last cluster should be
+    /// dropped.
+    Truncated,
+}
+
+impl<R: BufRead> ClstrParser<R> {
+    /// Reads up to the next header or sequence line.
+    fn next_record(&mut self) -> Option<Result<Record>> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_until(b'\n', &mut self.buf) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => return Some(Err(self.state.read_error(e))),
+            };
+
+            if let Some(record) = self.state.parse_line(&self.buf) {
+                return Some(record);
+            }
+        }
+    }
+
+    /// Turns this parser into an iterator over `(cluster_id, sequence)`
+    /// pairs, one line at a time, without gathering whole clusters.
+    pub fn into_sequences(self) -> SequenceRecords<R> {
+        SequenceRecords { parser: self }
+    }
+}
+
+impl<R: BufRead> Iterator for ClstrParser<R> {
+    type Item = Result<Cluster>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(record) = self.next_record() {
+            match record {
+                Ok(record) => {
+                    if let Some(c) = gather_record(&mut self.current_cluster, record) {
+                        return Some(Ok(c));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        self.current_cluster.take().map(Ok)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // only a cluster already begun is sure to be yielded
+        (usize::from(self.current_cluster.is_some()), None)
+    }
+}
+
+/// Iterator over the sequences of a `.clstr` file paired with their
+/// cluster IDs, returned by [`ClstrParser::into_sequences`].
+pub struct SequenceRecords<R: BufRead> {
+    parser: ClstrParser<R>,
+}
+
+impl<R: BufRead> Iterator for SequenceRecords<R> {
+    type Item = Result<(usize, Sequence)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(record) = self.parser.next_record() {
+            match record {
+                Ok(Record::Header(..) | Record::Truncated) => continue,
+                Ok(Record::Sequence(cluster_id, _, seq)) => return Some(Ok((cluster_id, seq))),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+/// Parse the cluster ID from a header line such as `>Cluster 7`.
+fn parse_cluster_header(line: &str) -> Result<usize> {
+    parse_cluster_id(line, false).ok_or_else(|| {
+        Error::new(ErrorKind::ReadRecord(format!(
+            "Invalid cluster header: {line}"
+        )))
+    })
+}
+
+/// Parse the cluster ID from a header line, matching `>Cluster`
+/// case-insensitively if `ignore_case` is set.
+fn parse_cluster_id(line: &str, ignore_case: bool) -> Option<usize> {
+    split_header(line, ignore_case).and_then(|(id, _)| id.parse().ok())
+}
+
+/// Split a header line such as `>Cluster 7 phage-like` into the token after
+/// `>Cluster` and the note after that, if any.
+fn split_header(line: &str, ignore_case: bool) -> Option<(&str, Option<&str>)> {
+    const PREFIX: &str = ">Cluster";
+    let prefix = line.get(..PREFIX.len())?;
+    if prefix != PREFIX && !(ignore_case && prefix.eq_ignore_ascii_case(PREFIX)) {
+        return None;
+    }
+
+    let rest = &line[PREFIX.len()..];
+    if !rest.starts_with(|c: char| c.is_ascii_whitespace()) {
+        return None;
+    }
+    let rest = rest.trim();
+    Some(match rest.split_once(|c: char| c.is_ascii_whitespace()) {
+        Some((id, note)) => (id, Some(note.trim_start())),
+        None => (rest, None),
+    })
+}
+
+/// Returns the 1-based column at which `token`, a subslice of `line`, starts.
+fn column_of(line: &str, token: &str) -> usize {
+    token.as_ptr() as usize - line.as_ptr() as usize + 1
+}
+
+/// Parse a single sequence line from a cluster file, returning the index from
+/// its first column along with the sequence. `line_no` is only used to locate
+/// errors.
+fn parse_sequence_line(
+    line: &str,
+    line_no: u64,
+    options: &ParseOptions,
+) -> Result<(usize, Sequence)> {
+    // The fields are picked off in place rather than collected, as this runs
+    // once per line.
+    let invalid_line = || parse_error(line_no, None, format!("invalid sequence line '{line}'"));
+
+    let mut fields = line.split_ascii_whitespace();
+    let (Some(index_field), Some(len_field)) = (fields.next(), fields.next()) else {
+        return Err(invalid_line());
+    };
+
+    let index = index_field.parse::<usize>().map_err(|_| {
+        parse_error(
+            line_no,
+            Some(column_of(line, index_field)),
+            format!("invalid index '{index_field}'"),
+        )
+    })?;
+
+    // len_field is something like "4481aa," or "100nt,", though rewritten
+    // files may have split off the comma as in "4481aa ,"
+    let invalid_length = || {
+        parse_error(
+            line_no,
+            Some(column_of(line, len_field)),
+            format!("invalid length format '{len_field}'"),
+        )
+    };
+
+    let len_token = len_field.trim_end_matches(',');
+    let split_pos = len_token
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(len_token.len());
+    let (len_num, len_unit) = len_token.split_at(split_pos);
+
+    let length = len_num.parse::<u32>().map_err(|_| invalid_length())?;
+
+    let unit = match len_unit {
+        "aa" => LengthUnit::Aa,
+        "nt" => LengthUnit::Nt,
+        _ => return Err(invalid_length()),
+    };
+
+    // the ID follows the first '>' after the length, as in
+    // ">sp|P0C6T5|R1A_BCHK5...", sometimes with a space after the '>'. It
+    // runs to the "...", and may itself contain spaces when CD-HIT cut a
+    // description to its `-d` length.
+    let after_len = &line[column_of(line, len_field) - 1 + len_field.len()..];
+    let id_start = after_len.find('>').ok_or_else(invalid_line)?;
+    let id_field = after_len[id_start + 1..].trim_start();
+    let (id, rest) = match id_field.find("...") {
+        Some(end) => (&id_field[..end], &id_field[end + 3..]),
+        None => id_field.split_at(
+            id_field
+                .find(|c: char| c.is_ascii_whitespace())
+                .unwrap_or(id_field.len()),
+        ),
+    };
+    if id.is_empty() {
+        return Err(invalid_line());
+    }
+    let id = id.to_string();
+
+    // the representative is marked by a '*' token after the "...", not by
+    // the line ending in '*', which an ID like "ORF7a*" would. A marker after
+    // an identity is still taken, and left to `validate` to flag.
+    let is_representative = rest.split_ascii_whitespace().next_back() == Some("*");
+
+    // only the text after the ID is searched, so an " at " in the ID is not
+    // mistaken for the identity
+    let (strand, identity) = parse_identity(line, rest, line_no)?;
+    let strand = strand.filter(|_| options.cd_hit_est);
+    // `-d` counts the terminating NUL of CD-HIT's buffer, so names are cut to
+    // one character less
+    let maybe_truncated =
+        options.description_length > 0 && id.chars().count() >= options.description_length - 1;
+
+    Ok((
+        index,
+        Sequence {
+            length,
+            unit,
+            id,
+            identity,
+            strand,
+            is_representative,
+            maybe_truncated,
+        },
+    ))
+}
+
+/// Parse the identity after " at " in `after_id`, the part of `line` after
+/// the sequence ID, along with the strand when it has a CD-HIT-EST `+/` or
+/// `-/` prefix.
+fn parse_identity(
+    line: &str,
+    after_id: &str,
+    line_no: u64,
+) -> Result<(Option<Strand>, Option<f32>)> {
+    let at_pos = match after_id.find(" at ") {
+        Some(p) => p,
+        None => return Ok((None, None)),
+    };
+
+    // Take the token immediately after " at "
+    let rest = &after_id[at_pos + 4..];
+    let raw = rest.split_ascii_whitespace().next().unwrap_or("");
+    let token = raw.trim_end_matches('%').trim();
+
+    if token.is_empty() {
+        return Ok((None, None));
+    }
+
+    let mut strand = None;
+
+    // Handle tokens that contain "/", e.g.
+    //   "-/97.54"
+    //   "+/95.70"
+    //   "99.89/100"
+    let primary = if let Some((a, b)) = token.split_once('/') {
+        let a = a.trim();
+        let b = b.trim();
+
+        // cd-hit-est style: "-/97.54" or "+/95.70"
+        // Use the numeric part (b) as identity.
+        if a == "-" || a == "+" || a.is_empty() {
+            strand = match a {
+                "+" => Some(Strand::Forward),
+                "-" => Some(Strand::Reverse),
+                _ => None,
+            };
+            b
+        } else {
+            // e.g. "99.89/100" – use the first numeric part
+            a
+        }
+    } else {
+        // Simple case: "99.89"
+        token
+    };
+
+    // `f32::parse` takes "nan" and "inf", which are no identity
+    let identity = parse_identity_token(primary)
+        .ok()
+        .filter(|identity| identity.is_none_or(f32::is_finite))
+        .ok_or_else(|| {
+            parse_error(
+                line_no,
+                Some(column_of(line, raw)),
+                format!("invalid identity '{raw}'"),
+            )
+        })?;
+    Ok((strand, identity))
+}
+
+fn parse_identity_token(token: &str) -> std::result::Result<Option<f32>, ParseFloatError> {
+    let t = token.trim();
+
+    // Strip leading "+"
+    let t = t.trim_start_matches('+');
+
+    // A bare "-" means "no similarity"
+    if t == "-" || t.is_empty() {
+        return Ok(None);
+    }
+
+    let value = t.parse::<f32>()?;
+    Ok(Some(value))
+}
+
+/// The magic bytes at the start of a gzip stream.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+/// The magic bytes at the start of a bzip2 stream.
+#[cfg(feature = "compression")]
+const BZIP2_MAGIC: &[u8] = b"BZh";
+/// The magic bytes at the start of an xz stream.
+#[cfg(feature = "compression")]
+const XZ_MAGIC: &[u8] = &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+/// The magic bytes at the start of a zstd frame.
+#[cfg(feature = "compression")]
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// The reader behind [`from_path`], which is either the plain file or a
+/// decompressing reader over it.
+pub enum ClstrReader {
+    /// An uncompressed file.
+    Plain(BufReader<File>),
+    /// Standard input, read as is.
+    Stdin(io::StdinLock<'static>),
+    /// A gzip-compressed file, including multi-member files such as those
+    /// written by `bgzip`.
+    Gzip(BufReader<MultiGzDecoder<BufReader<File>>>),
+    /// A bzip2-compressed file.
+    #[cfg(feature = "compression")]
+    Bzip2(BufReader<bzip2::bufread::MultiBzDecoder<BufReader<File>>>),
+    /// An xz-compressed file.
+    #[cfg(feature = "compression")]
+    Xz(BufReader<xz2::bufread::XzDecoder<BufReader<File>>>),
+    /// A zstd-compressed file.
+    #[cfg(feature = "compression")]
+    Zstd(BufReader<zstd::stream::read::Decoder<'static, BufReader<File>>>),
+}
+
+/// Runs `$body` with `$r` bound to the inner reader of any `ClstrReader`.
+macro_rules! with_inner {
+    ($reader:expr, $r:ident => $body:expr) => {
+        match $reader {
+            ClstrReader::Plain($r) => $body,
+            ClstrReader::Stdin($r) => $body,
+            ClstrReader::Gzip($r) => $body,
+            #[cfg(feature = "compression")]
+            ClstrReader::Bzip2($r) => $body,
+            #[cfg(feature = "compression")]
+            ClstrReader::Xz($r) => $body,
+            #[cfg(feature = "compression")]
+            ClstrReader::Zstd($r) => $body,
+        }
+    };
+}
+
+impl ClstrReader {
+    /// Opens the file at `path`, decompressing it if it has a `.gz` extension
+    /// or starts with the gzip magic bytes. With the `compression` feature,
+    /// bzip2, xz and zstd input is also detected from its magic bytes.
+    ///
+    /// A path of `-` reads standard input, which is never decompressed.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<ClstrReader> {
+        let path = path.as_ref();
+        if path == Path::new("-") {
+            return Ok(ClstrReader::Stdin(io::stdin().lock()));
+        }
+
+        // name the file in the error, so it is not mistaken for stdin
+        let file = File::open(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        let mut reader = BufReader::new(file);
+
+        let has_gz_extension = path.extension().and_then(|s| s.to_str()) == Some("gz");
+        let magic = reader.fill_buf()?;
+
+        if has_gz_extension || magic.starts_with(GZIP_MAGIC) {
+            let decoder = MultiGzDecoder::new(reader);
+            return Ok(ClstrReader::Gzip(BufReader::new(decoder)));
+        }
+
+        #[cfg(feature = "compression")]
+        {
+            if magic.starts_with(BZIP2_MAGIC) {
+                let decoder = bzip2::bufread::MultiBzDecoder::new(reader);
+                return Ok(ClstrReader::Bzip2(BufReader::new(decoder)));
+            }
+            if magic.starts_with(XZ_MAGIC) {
+                let decoder = xz2::bufread::XzDecoder::new_multi_decoder(reader);
+                return Ok(ClstrReader::Xz(BufReader::new(decoder)));
+            }
+            if magic.starts_with(ZSTD_MAGIC) {
+                let decoder = zstd::stream::read::Decoder::with_buffer(reader)?;
+                return Ok(ClstrReader::Zstd(BufReader::new(decoder)));
+            }
+        }
+
+        Ok(ClstrReader::Plain(reader))
+    }
+}
+
+impl Read for ClstrReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        with_inner!(self, r => r.read(buf))
+    }
+}
+
+impl BufRead for ClstrReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        with_inner!(self, r => r.fill_buf())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        with_inner!(self, r => r.consume(amt))
+    }
+}
+
+/// Function to parse a `.clstr` file from a path. Gzip-compressed files are
+/// decompressed transparently, and a path of `-` reads standard input.
+pub fn from_path<P: AsRef<Path>>(path: P) -> Result<ClstrParser<ClstrReader>> {
+    let reader = ClstrReader::from_path(path)?;
+    let input_len = match &reader {
+        ClstrReader::Plain(file) => Some(file.get_ref().metadata()?.len()),
+        _ => None,
+    };
+
+    let parser = ClstrParser::new(reader);
+    Ok(match input_len {
+        Some(len) => parser.with_input_len(len),
+        None => parser,
+    })
+}
+
+/// Function to parse a `.clstr` file from standard input.
+pub fn from_stdin() -> ClstrParser<io::StdinLock<'static>> {
+    ClstrParser::new(io::stdin().lock())
+}
+
+/// Function to parse a `.clstr` file from a reader.
+pub fn from_reader<R: BufRead>(reader: R) -> ClstrParser<R> {
+    ClstrParser::new(reader)
+}
+
+/// Function to parse a `.clstr` file by memory-mapping it, letting the OS
+/// page cache serve repeated passes over the same large file. Compressed
+/// files are not supported.
+///
+/// The file must not be modified or truncated while the parser is alive.
+#[cfg(feature = "mmap")]
+pub fn from_mmap<P: AsRef<Path>>(path: P) -> Result<ClstrParser<Cursor<memmap2::Mmap>>> {
+    let file = File::open(path)?;
+    // SAFETY: the mapping is only read, and the caller is told above not to
+    // modify the file underneath it.
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    Ok(ClstrParser::new(Cursor::new(mmap)))
+}
+
+/// Function to parse `.clstr` data held in memory, e.g. a `&str` or `String`.
+pub fn from_str<S: AsRef<[u8]>>(input: S) -> ClstrParser<Cursor<S>> {
+    ClstrParser::new(Cursor::new(input))
+}
+
+/// Struct to write `.clstr` format files.
+pub struct ClstrWriter<W: Write> {
+    writer: W,
+    /// Whether to write each cluster's header note after its ID.
+    header_notes: bool,
+    /// The unit every sequence must have, taken from the first sequence
+    /// written unless set.
+    unit: Option<LengthUnit>,
+    /// Whether sequences may have differing units.
+    mixed_units: bool,
+}
+
+impl<W: Write> ClstrWriter<W> {
+    /// Creates a new `ClstrWriter`.
+    pub fn new(writer: W) -> Self {
+        ClstrWriter {
+            writer,
+            header_notes: false,
+            unit: None,
+            mixed_units: false,
+        }
+    }
+
+    /// Sets the unit every sequence written must have. Without this, the
+    /// unit of the first sequence written is expected of the rest.
+    pub fn with_unit(mut self, unit: LengthUnit) -> Self {
+        self.unit = Some(unit);
+        self
+    }
+
+    /// Sets whether sequences of differing units may be written, each with
+    /// its own suffix. Mixing units is an error by default.
+    pub fn allow_mixed_units(mut self, mixed_units: bool) -> Self {
+        self.mixed_units = mixed_units;
+        self
+    }
+
+    /// Sets whether [`Cluster::header_note`] is written after the cluster ID,
+    /// so that annotated headers round-trip. Notes are not written by default,
+    /// matching CD-HIT's own output.
+    pub fn with_header_notes(mut self, header_notes: bool) -> Self {
+        self.header_notes = header_notes;
+        self
+    }
+
+    /// Writes a cluster to the `.clstr` format. Sequences are numbered by
+    /// their position in the cluster, whatever index they were parsed with,
+    /// so writing normalizes hand-edited files.
+    pub fn write_cluster(&mut self, cluster: &Cluster) -> Result<()> {
+        // check the cluster before writing anything, so that a rejected
+        // cluster leaves no partial output
+        let invalid = |msg: String| {
+            Err(Error::new(ErrorKind::InvalidCluster {
+                cluster_id: cluster.cluster_id(),
+                msg,
+            }))
+        };
+        for seq in cluster.sequences() {
+            if seq.id().is_empty() {
+                return invalid("empty sequence ID".to_string());
+            }
+            if seq.id().contains(['\n', '\r']) {
+                return invalid(format!("line break in sequence ID {:?}", seq.id()));
+            }
+        }
+        match cluster.header_note() {
+            Some(note) if self.header_notes && note.contains(['\n', '\r']) => {
+                return invalid(format!("line break in header note {note:?}"));
+            }
+            _ => {}
+        }
+        if !self.mixed_units {
+            for seq in cluster.sequences() {
+                let expected = *self.unit.get_or_insert(seq.unit);
+                if seq.unit != expected {
+                    return Err(Error::new(ErrorKind::MixedUnits {
+                        cluster_id: cluster.cluster_id(),
+                        expected,
+                        found: seq.unit,
+                    }));
+                }
+            }
+        }
+
+        // Write the cluster header: >Cluster <ID>, then any note
+        write!(self.writer, ">Cluster {}", cluster.cluster_id())?;
+        match cluster.header_note() {
+            Some(note) if self.header_notes => writeln!(self.writer, " {note}")?,
+            _ => writeln!(self.writer)?,
+        }
+
+        // Write each sequence in the cluster, formatted by its `Display`.
+        for (index, seq) in cluster.sequences().iter().enumerate() {
+            writeln!(self.writer, "{index}    {seq}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes each cluster in `clusters` with [`ClstrWriter::write_cluster`],
+    /// then flushes the writer.
+    pub fn write_clusters<'a, I: IntoIterator<Item = &'a Cluster>>(
+        &mut self,
+        clusters: I,
+    ) -> Result<()> {
+        for cluster in clusters {
+            self.write_cluster(cluster)?;
+        }
+        self.flush()?;
+        Ok(())
+    }
+
+    /// Sorts `clusters` with `compare`, keeping the order of equal clusters,
+    /// then writes them as [`ClstrWriter::write_clusters`] does.
+    pub fn write_clusters_sorted<'a, I, F>(&mut self, clusters: I, compare: F) -> Result<()>
+    where
+        I: IntoIterator<Item = &'a Cluster>,
+        F: Fn(&Cluster, &Cluster) -> std::cmp::Ordering,
+    {
+        let mut clusters: Vec<&Cluster> = clusters.into_iter().collect();
+        clusters.sort_by(|a, b| compare(a, b));
+        self.write_clusters(clusters)
+    }
+
+    /// Finalize the writer by flushing any remaining output.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Flushes the writer and returns the underlying writer. Unlike
+    /// [`BufWriter::into_inner`], the writer is lost if flushing fails.
+    pub fn into_inner(mut self) -> Result<W> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Finish> ClstrWriter<W> {
+    /// Finishes the output with [`Finish::try_finish`] and returns the
+    /// underlying writer. For gzip output this writes the trailer, which a
+    /// flush does not, so call this rather than relying on drop to see any
+    /// error.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.try_finish()?;
+        Ok(self.writer)
+    }
+}
+
+/// Output that may need more than a flush once everything is written, such
+/// as a compressed stream with a trailer.
+pub trait Finish: Write {
+    /// Writes anything left to end the output, then flushes it. No more may
+    /// be written afterwards. Defaults to a flush.
+    fn try_finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl<W: Write> Finish for GzEncoder<W> {
+    fn try_finish(&mut self) -> io::Result<()> {
+        GzEncoder::try_finish(self)?;
+        self.get_mut().flush()
+    }
+}
+
+impl Finish for ClstrOutput {
+    fn try_finish(&mut self) -> io::Result<()> {
+        match self {
+            ClstrOutput::Plain(w) => w.flush(),
+            ClstrOutput::Gzip(w) => Finish::try_finish(w),
+        }
+    }
+}
+
+impl<W: Write> Finish for BufWriter<W> {}
+impl Finish for File {}
+impl Finish for Vec<u8> {}
+impl Finish for io::Stdout {}
+impl Finish for io::StdoutLock<'_> {}
+impl<W: Finish + ?Sized> Finish for &mut W {
+    fn try_finish(&mut self) -> io::Result<()> {
+        (**self).try_finish()
+    }
+}
+
+/// The writer behind [`to_path`], which is either the plain file or a
+/// compressing writer over it.
+pub enum ClstrOutput {
+    /// An uncompressed file.
+    Plain(BufWriter<File>),
+    /// A gzip-compressed file. The gzip trailer is written by
+    /// [`ClstrWriter::finish`], or when the writer is dropped, ignoring any
+    /// error.
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl Write for ClstrOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ClstrOutput::Plain(w) => w.write(buf),
+            ClstrOutput::Gzip(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ClstrOutput::Plain(w) => w.flush(),
+            ClstrOutput::Gzip(w) => w.flush(),
+        }
+    }
+}
+
+/// Helper function to create a writer from a file path. Output is
+/// gzip-compressed if the path has a `.gz` extension.
+pub fn to_path<P: AsRef<Path>>(path: P) -> Result<ClstrWriter<ClstrOutput>> {
+    let path = path.as_ref();
+    let file = BufWriter::new(File::create(path)?);
+
+    let output = if path.extension().and_then(|s| s.to_str()) == Some("gz") {
+        ClstrOutput::Gzip(GzEncoder::new(file, Compression::default()))
+    } else {
+        ClstrOutput::Plain(file)
+    };
+
+    Ok(ClstrWriter::new(output))
+}
+
+/// Helper function to create a gzip-compressed writer to a file path,
+/// whatever its extension, at the default compression level. Call
+/// [`ClstrWriter::finish`] once done to write the gzip trailer.
+pub fn to_gz_path<P: AsRef<Path>>(path: P) -> Result<ClstrWriter<GzEncoder<File>>> {
+    to_gz_path_with_level(path, Compression::default())
+}
+
+/// Helper function to create a gzip-compressed writer to a file path at
+/// the given compression level, as [`to_gz_path`] does.
+pub fn to_gz_path_with_level<P: AsRef<Path>>(
+    path: P,
+    level: Compression,
+) -> Result<ClstrWriter<GzEncoder<File>>> {
+    let file = File::create(path)?;
+    Ok(ClstrWriter::new(GzEncoder::new(file, level)))
+}
+
+/// Helper function to create a writer over any [`Write`], such as standard
+/// output or an in-memory buffer.
+pub fn to_writer<W: Write>(writer: W) -> ClstrWriter<W> {
+    ClstrWriter::new(writer)
+}
+
+/// Writes a tab-separated table to `writer` with a header row and then one
+/// row per sequence: its cluster ID, ID, length, identity and whether it is
+/// the representative. A missing identity is written as `NA`.
+pub fn write_membership_tsv<W: Write>(mut writer: W, clusters: &[Cluster]) -> Result<()> {
+    let options = MembershipOptions::default();
+    write_membership_header(&mut writer, &options)?;
+    for cluster in clusters {
+        write_membership_rows(&mut writer, cluster, &options)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the same table as [`write_membership_tsv`] while parsing, holding
+/// only one cluster in memory at a time.
+pub fn stream_membership_tsv<R: BufRead, W: Write>(
+    parser: ClstrParser<R>,
+    writer: W,
+) -> Result<()> {
+    write_membership_table(parser, writer, &MembershipOptions::default())
+}
+
+/// The layout of the table written by [`write_membership_table`]. The
+/// default is the tab-separated table of [`write_membership_tsv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MembershipOptions {
+    /// Whether to start with a header row. Defaults to `true`.
+    pub header: bool,
+    /// The separator between fields. Defaults to a tab.
+    pub delimiter: char,
+    /// Whether to write a row for each representative. Defaults to `true`.
+    pub include_representatives: bool,
+    /// Whether to add a last column with the CD-HIT-EST strand, `+`, `-` or
+    /// `NA`. Defaults to `false`.
+    pub strand: bool,
+}
+
+impl Default for MembershipOptions {
+    fn default() -> Self {
+        MembershipOptions {
+            header: true,
+            delimiter: '\t',
+            include_representatives: true,
+            strand: false,
+        }
+    }
+}
+
+/// Writes the membership table of [`write_membership_tsv`] while reading
+/// `clusters`, laid out as `options` says. One cluster is held at a time, and
+/// the first read or write error stops the writing.
+pub fn write_membership_table<I, W>(
+    clusters: I,
+    mut writer: W,
+    options: &MembershipOptions,
+) -> Result<()>
+where
+    I: IntoIterator<Item = Result<Cluster>>,
+    W: Write,
+{
+    write_membership_header(&mut writer, options)?;
+    for cluster in clusters {
+        write_membership_rows(&mut writer, &cluster?, options)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// The columns of the membership table, before the optional strand.
+const MEMBERSHIP_COLUMNS: [&str; 5] = [
+    "cluster_id",
+    "sequence_id",
+    "length",
+    "identity",
+    "is_representative",
+];
+
+/// Write the header row of the membership table, if `options` asks for one.
+fn write_membership_header<W: Write>(writer: &mut W, options: &MembershipOptions) -> Result<()> {
+    if !options.header {
+        return Ok(());
+    }
+    let d = options.delimiter;
+    write!(writer, "{}", MEMBERSHIP_COLUMNS.join(&d.to_string()))?;
+    if options.strand {
+        write!(writer, "{d}strand")?;
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Write a row of the membership table for each sequence in `cluster`.
+fn write_membership_rows<W: Write>(
+    writer: &mut W,
+    cluster: &Cluster,
+    options: &MembershipOptions,
+) -> Result<()> {
+    let d = options.delimiter;
+    for seq in cluster {
+        if seq.is_representative() && !options.include_representatives {
+            continue;
+        }
+        let identity = seq
+            .identity()
+            .map_or("NA".to_string(), |i| format!("{i:.2}"));
+        write!(
+            writer,
+            "{}{d}{}{d}{}{d}{}{d}{}",
+            cluster.cluster_id(),
+            seq.id(),
+            seq.length(),
+            identity,
+            seq.is_representative()
+        )?;
+        if options.strand {
+            let strand = match seq.strand() {
+                Some(Strand::Forward) => "+",
+                Some(Strand::Reverse) => "-",
+                None => "NA",
+            };
+            write!(writer, "{d}{strand}")?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Writes an edge list to `writer` with a header row and then one row per
+/// edge of [`Cluster::to_edges`], its fields separated by `delimiter`: the
+/// representative ID, the member ID and the identity to two decimal places,
+/// as in [`write_membership_table`], or `NA` if missing.
+/// Clusters without a representative are handled as `on_missing` says, and
+/// the number skipped is returned. One cluster is held at a time, and the
+/// first error stops the writing.
+pub fn write_edge_list<I, W>(
+    clusters: I,
+    mut writer: W,
+    delimiter: char,
+    on_missing: MissingRepresentative,
+) -> Result<u64>
+where
+    I: IntoIterator<Item = Result<Cluster>>,
+    W: Write,
+{
+    writeln!(writer, "rep_id{delimiter}member_id{delimiter}identity")?;
+    let mut skipped = 0;
+    for cluster in clusters {
+        let cluster = cluster?;
+        let hub = match on_missing {
+            MissingRepresentative::Longest => cluster.representative_or_longest(),
+            MissingRepresentative::Skip => cluster.get_representative(),
+        };
+        if hub.is_none() && cluster.size() > 0 {
+            skipped += 1;
+        }
+        for edge in cluster.edges_from(hub) {
+            let identity = edge
+                .identity
+                .map_or("NA".to_string(), |i| format!("{i:.2}"));
+            writeln!(
+                writer,
+                "{}{delimiter}{}{delimiter}{identity}",
+                edge.rep_id, edge.member_id
+            )?;
+        }
+    }
+    writer.flush()?;
+    Ok(skipped)
+}
+
+/// The cluster a sequence belongs to, as found by [`membership_map`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Membership {
+    /// The ID of the cluster.
+    pub cluster_id: usize,
+    /// Whether the sequence is the representative of the cluster.
+    pub is_representative: bool,
+    /// The percentage identity to the representative, if available.
+    pub identity: Option<f32>,
+}
+
+/// Maps each sequence ID to the cluster it is in, parsing one cluster at a
+/// time. A sequence ID seen again overwrites the earlier entry, unless
+/// `error_on_duplicates` is set, in which case it is an error.
+pub fn membership_map<R: BufRead>(
+    parser: ClstrParser<R>,
+    error_on_duplicates: bool,
+) -> Result<HashMap<String, Membership>> {
+    let mut map: HashMap<String, Membership> = HashMap::new();
+    for cluster in parser {
+        let cluster = cluster?;
+        for seq in cluster.sequences {
+            let membership = Membership {
+                cluster_id: cluster.cluster_id,
+                is_representative: seq.is_representative,
+                identity: seq.identity,
+            };
+            match map.entry(seq.id) {
+                Entry::Occupied(entry) if error_on_duplicates => {
+                    return Err(Error::new(ErrorKind::ReadRecord(format!(
+                        "sequence '{}' found in cluster {} and again in cluster {}",
+                        entry.key(),
+                        entry.get().cluster_id,
+                        cluster.cluster_id
+                    ))));
+                }
+                Entry::Occupied(mut entry) => {
+                    entry.insert(membership);
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(membership);
+                }
+            }
+        }
+    }
+    Ok(map)
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Cursor;
+
+    /// Returns a path in the temporary directory for the file `name`, unique
+    /// to this process so that concurrent test runs do not collide.
+    pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("clstr_test_{}_{name}", std::process::id()))
+    }
+
+    #[test]
+    fn test_clstr_parsing() {
+        let data = b">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7126aa, >sp|P0C6W1|R1AB_BC133... at 66.94%
+2    7119aa, >sp|P0C6W3|R1AB_BCHK4... at 67.17%
+3    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+4    307aa, >sp|Q9WQ77|R1AB_CVRSD... at 76.22%
+>Cluster 1
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... at 99.91%
+1    4441aa, >sp|P0C6U4|R1A_CVHN2... at 81.47%
+2    4421aa, >sp|P0C6U5|R1A_CVHN5... at 81.52%
+" as &[u8];
+
+        let mut parser = ClstrParser::new(data);
+
+        let cluster0 = parser.next().unwrap().unwrap();
+        assert_eq!(cluster0.cluster_id(), 0);
+        assert_eq!(cluster0.size(), 5);
+        assert_eq!(cluster0.sequences()[0].id(), "sp|P0C6T5|R1A_BCHK5");
+        assert_eq!(cluster0.sequences()[0].identity(), Some(99.89));
+        assert!(!cluster0.sequences()[0].is_representative());
+
+        assert_eq!(cluster0.sequences()[3].id(), "sp|P0C6W4|R1AB_BCHK5");
+        assert!(cluster0.sequences()[3].is_representative());
+        assert_eq!(cluster0.sequences()[3].identity(), None);
+
+        let cluster1 = parser.next().unwrap().unwrap();
+        assert_eq!(cluster1.cluster_id(), 1);
+        assert_eq!(cluster1.size(), 3);
+        assert_eq!(cluster1.sequences()[0].id(), "sp|P0C6U3|R1A_CVHN1");
+        assert_eq!(cluster1.sequences()[0].identity(), Some(99.91));
+        assert!(!cluster1.sequences()[0].is_representative());
+
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn test_cluster_ids_from_header() {
+        let data = b">Cluster 3
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... *
+>Cluster 7
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... *
+>Cluster seven
+0    4441aa, >sp|P0C6U4|R1A_CVHN2... *
+" as &[u8];
+
+        let mut parser = ClstrParser::new(data);
+        assert_eq!(parser.next().unwrap().unwrap().cluster_id(), 3);
+
+        // the bad header is reached while cluster 7 is still being collected
+        let err = parser.next().unwrap().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Parse { line: 5, .. }));
+        assert_eq!(
+            err.to_string(),
+            "parse error at line 5: invalid cluster header '>Cluster seven'"
+        );
+    }
+
+    #[test]
+    fn test_error_source() {
+        use std::error::Error as _;
+
+        let err = Error::from("x".parse::<u32>().unwrap_err());
+        assert!(err.source().unwrap().is::<ParseIntError>());
+        let err = Error::from(io::Error::other("inner"));
+        assert_eq!(err.source().unwrap().to_string(), "inner");
+        assert!(parse_error(1, None, "bad".to_string()).source().is_none());
+
+        // the chain is visible through a `dyn Error`, as reporters walk it
+        let err: Box<dyn std::error::Error> = Box::new(Error::from(io::Error::other("inner")));
+        let chain: Vec<String> =
+            std::iter::successors(Some(&*err as &dyn std::error::Error), |&e| e.source())
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(chain, vec!["I/O error - inner", "inner"]);
+    }
+
+    #[test]
+    fn test_writer_rejects_invalid_cluster() {
+        let mut cluster = from_str(">Cluster 4\n0    90nt, >a... *\n")
+            .next()
+            .unwrap()
+            .unwrap();
+        cluster.sequences_mut()[0].set_id("a\nb".to_string());
+        let mut writer = ClstrWriter::new(Vec::new());
+        let err = writer.write_cluster(&cluster).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::InvalidCluster { cluster_id: 4, .. }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "writing cluster 4 - line break in sequence ID \"a\\nb\""
+        );
+
+        cluster.sequences_mut()[0].set_id(String::new());
+        assert!(writer.write_cluster(&cluster).is_err());
+        assert!(writer.into_inner().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_size_hint() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_nt.clstr");
+        let len = std::fs::metadata(path).unwrap().len();
+        let mut parser = from_path(path).unwrap();
+        assert_eq!(parser.estimated_clusters(), Some((len / 200) as usize));
+        assert_eq!(parser.size_hint(), (0, None));
+
+        // the estimate shrinks as the input is read, and the cluster begun
+        // on reading the next header is sure to come
+        parser.next().unwrap().unwrap();
+        assert!(parser.estimated_clusters().unwrap() <= (len / 200) as usize);
+        assert_eq!(parser.size_hint(), (1, None));
+        assert_eq!(parser.by_ref().count(), 3);
+        assert_eq!(parser.size_hint(), (0, None));
+
+        assert_eq!(from_str(">Cluster 0\n").estimated_clusters(), None);
+        let parser = from_str(">Cluster 0\n").with_input_len(2000);
+        assert_eq!(parser.estimated_clusters(), Some(10));
+        assert_eq!(parser.size_hint(), (0, None));
+    }
+
+    #[test]
+    fn test_read_error_offset() {
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk on fire"))
+            }
+        }
+
+        let data = ">Cluster 0\n0    4481aa, >sp|P0C6T5|R1A_BCHK5... *\n";
+        let reader = BufReader::new(data.as_bytes().chain(Broken));
+        let err = ClstrParser::new(reader).next().unwrap().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("I/O error - disk on fire (at byte offset {})", data.len())
+        );
+    }
+
+    #[test]
+    fn test_identity_summaries() {
+        let data = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7126aa, >sp|P0C6W1|R1AB_BC133... at 66.94%
+2    7119aa, >sp|P0C6W3|R1AB_BCHK4... at 67.17%
+3    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+>Cluster 1
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... *
+";
+        let clusters: Vec<Cluster> = from_str(data).collect::<Result<_>>().unwrap();
+
+        assert_eq!(clusters[0].min_identity(), Some(66.94));
+        assert_eq!(clusters[0].max_identity(), Some(99.89));
+        let mean = clusters[0].average_identity().unwrap();
+        assert!((mean - (99.89 + 66.94 + 67.17) / 3.0).abs() < 1e-4);
+
+        assert_eq!(clusters[1].min_identity(), None);
+        assert_eq!(clusters[1].max_identity(), None);
+        assert_eq!(clusters[1].average_identity(), None);
+
+        assert_eq!(
+            clusters[0].total_sequence_length(),
+            4481 + 7126 + 7119 + 7182
+        );
+        assert_eq!(clusters[0].representative_length(), Some(7182));
+        assert_eq!(clusters[1].total_sequence_length(), 4471);
+
+        let stats = clusters[0].stats();
+        assert_eq!(stats.total_length, 4481 + 7126 + 7119 + 7182);
+        assert_eq!(stats.median_length, (7119.0 + 7126.0) / 2.0);
+        assert_eq!((stats.min_length, stats.max_length), (4481, 7182));
+        assert_eq!(stats.min_identity, Some(66.94));
+        assert_eq!(stats.representative_length, Some(7182));
+
+        let stats = clusters[1].stats();
+        assert_eq!(stats.mean_length, 4471.0);
+        assert_eq!(stats.median_length, 4471.0);
+        assert_eq!(stats.mean_identity, None);
+        assert_eq!(stats.min_identity, None);
+    }
+
+    #[test]
+    fn test_cluster_get() {
+        let data = ">Cluster 3
+0    4481aa, >m... at 99.89%
+1    7126aa, >b... at 66.94%
+2    7182aa, >z... *
+3    4471aa, >b... at 90.00%
+";
+        let mut cluster = from_str(data).next().unwrap().unwrap();
+        assert_eq!(cluster.get("b").unwrap().identity(), Some(66.94));
+        assert!(cluster.contains("z"));
+        assert!(!cluster.contains("a"));
+        assert!(!cluster.contains("zz"));
+
+        // the index follows every change to the sequences
+        cluster.remove_sequence("b");
+        assert_eq!(cluster.get("b").unwrap().identity(), Some(90.0));
+        for seq in &mut cluster {
+            if seq.id() == "m" {
+                seq.set_id("a".to_string());
+            }
+        }
+        assert!(cluster.contains("a") && !cluster.contains("m"));
+        cluster.sort_by_length_desc(RepresentativePlacement::Sorted);
+        assert_eq!(cluster.get("z").unwrap().length(), 7182);
+        cluster.sequences_mut().clear();
+        assert!(!cluster.contains("z"));
+
+        // equality and clones are not affected by a built index
+        let a = from_str(data).next().unwrap().unwrap();
+        let b = a.clone();
+        assert!(a.contains("m"));
+        assert_eq!(a, b);
+        assert!(b.clone().contains("m"));
+    }
+
+    #[test]
+    fn test_cluster_mutation() {
+        let data = ">Cluster 3
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7126aa, >sp|P0C6W1|R1AB_BC133... at 66.94%
+2    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+";
+        let mut cluster = from_str(data).next().unwrap().unwrap();
+
+        // removing the representative promotes nothing
+        let rep = cluster.remove_sequence("sp|P0C6W4|R1AB_BCHK5").unwrap();
+        assert!(rep.is_representative());
+        assert!(cluster.get_representative().is_none());
+        assert!(cluster.remove_sequence("sp|P0C6W4|R1AB_BCHK5").is_none());
+
+        let err = cluster
+            .set_representative("sp|P0C6W4|R1AB_BCHK5")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cluster 3 has no sequence 'sp|P0C6W4|R1AB_BCHK5'"
+        );
+        cluster.set_representative("sp|P0C6T5|R1A_BCHK5").unwrap();
+        assert_eq!(
+            cluster.get_representative().unwrap().id(),
+            "sp|P0C6T5|R1A_BCHK5"
+        );
+        // the old identity to the representative is dropped
+        assert_eq!(
+            cluster.to_string().lines().nth(1),
+            Some("0    4481aa, >sp|P0C6T5|R1A_BCHK5... *")
+        );
+
+        // re-adding the old representative, then handing the flag back
+        cluster.push_sequence(rep);
+        assert_eq!(cluster.size(), 3);
+        cluster.set_representative("sp|P0C6W4|R1AB_BCHK5").unwrap();
+        let reps: Vec<&str> = cluster
+            .sequences()
+            .iter()
+            .filter(|s| s.is_representative())
+            .map(Sequence::id)
+            .collect();
+        assert_eq!(reps, vec!["sp|P0C6W4|R1AB_BCHK5"]);
+
+        cluster.clear_representative();
+        assert!(cluster.get_representative().is_none());
+        let longest = cluster.iter_mut().max_by_key(|s| s.length()).unwrap();
+        longest.set_representative(true);
+        longest.set_identity(None).unwrap();
+        assert_eq!(cluster.get_representative().unwrap().length(), 7182);
+
+        cluster.sequences_mut()[0].set_id("renamed".to_string());
+        assert!(cluster.contains_id("renamed"));
+        cluster.sequences_mut()[0].set_length(12);
+
+        let seq = &mut cluster.sequences_mut()[0];
+        seq.set_identity(Some(100.0)).unwrap();
+        for bad in [100.5, -1.0, f32::NAN] {
+            let err = seq.set_identity(Some(bad)).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::InvalidIdentity(_)));
+        }
+        assert_eq!(seq.identity(), Some(100.0));
+
+        assert!(!cluster.retain(|s| s.length() > 5000));
+        assert_eq!(cluster.size(), 2);
+        let mut copy = cluster.clone();
+        assert!(copy.retain(|s| !s.is_representative()));
+
+        let (drained, removed_representative) = cluster.clone().drain(1..);
+        assert_eq!(drained.len(), 1);
+        assert!(drained[0].is_representative());
+        assert!(removed_representative);
+        let mut copy = cluster.clone();
+        let (drained, removed_representative) = copy.drain(..1);
+        assert_eq!(drained[0].id(), "sp|P0C6W1|R1AB_BC133");
+        assert!(!removed_representative);
+        assert_eq!(copy.size(), 1);
+        assert!(copy.get_representative().is_some());
+        assert_eq!(cluster.sequences()[0].id(), "sp|P0C6W1|R1AB_BC133");
+    }
+
+    #[test]
+    fn test_cluster_equality() {
+        let data = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+";
+        let cluster = from_str(data).next().unwrap().unwrap();
+        let mut copy = cluster.clone();
+        assert_eq!(copy, cluster);
+
+        copy.sequences[0].identity = Some(99.89 + 5e-5);
+        assert!(copy.approx_eq(&cluster, 1e-4));
+        copy.sequences[0].identity = Some(99.5);
+        assert_ne!(copy, cluster);
+        assert!(!copy.approx_eq(&cluster, 1e-4));
+
+        // identities are left out of the hash
+        let set = HashSet::from([cluster.clone(), copy]);
+        assert_eq!(set.len(), 2);
+        let hash = |c: &Cluster| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            c.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert!(set.iter().all(|c| hash(c) == hash(&cluster)));
+
+        copy = cluster.clone();
+        copy.sequences.pop();
+        assert!(!copy.approx_eq(&cluster, 1.0));
+    }
+
+    #[test]
+    fn test_sequence_hash() {
+        let data = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+>Cluster 1
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... *
+>Cluster 2
+0    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+";
+        let sequences: HashSet<Sequence> = from_str(data).flat_map(|c| c.unwrap()).collect();
+        // the first line of cluster 1 differs from that of cluster 0
+        assert_eq!(sequences.len(), 3);
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for seq in &sequences {
+            *counts.entry(seq.id()).or_default() += 1;
+        }
+        assert_eq!(counts["sp|P0C6T5|R1A_BCHK5"], 2);
+        assert_eq!(counts["sp|P0C6W4|R1AB_BCHK5"], 1);
+    }
+
+    #[test]
+    fn test_merge_and_partition() {
+        let data = ">Cluster 0
+0    4481aa, >a... at 99.89%
+1    7182aa, >b... *
+>Cluster 1
+0    7126aa, >c... *
+>Cluster 2
+0    7119aa, >d... at 67.17%
+";
+        let clusters: Vec<Cluster> = from_str(data).collect::<Result<_>>().unwrap();
+
+        // merging a singleton demotes its representative
+        let merged = clusters[0].clone().merge(clusters[1].clone());
+        assert_eq!(merged.cluster_id(), 0);
+        assert_eq!(merged.size(), 3);
+        assert_eq!(merged.get_representative().unwrap().id(), "b");
+        assert!(!merged.sequences()[2].is_representative());
+        assert_eq!(merged.sequences()[2].identity(), None);
+
+        // without a representative of its own, the other's is kept
+        let merged = clusters[2].clone().merge(clusters[1].clone());
+        assert_eq!(merged.get_representative().unwrap().id(), "c");
+        let merged = clusters[2].clone().merge(clusters[2].clone());
+        assert!(merged.get_representative().is_none());
+
+        let (long, short) = clusters[0]
+            .clone()
+            .partition_by(7, 8, |s| s.length() > 5000);
+        assert_eq!((long.cluster_id(), short.cluster_id()), (7, 8));
+        assert_eq!(long.get_representative().unwrap().id(), "b");
+        assert!(short.get_representative().is_none());
+        assert_eq!(short.sequences()[0].id(), "a");
+
+        let (all, none) = clusters[0].clone().partition_by(7, 8, |_| true);
+        assert_eq!(all.size(), 2);
+        assert_eq!(none.size(), 0);
+    }
+
+    #[test]
+    fn test_jaccard_similarity() {
+        let data = ">Cluster 0
+0    100aa, >a... *
+1    100aa, >b... at 90.00%
+2    100aa, >c... at 90.00%
+>Cluster 1
+0    100aa, >b... *
+1    100aa, >c... at 90.00%
+2    100aa, >d... at 90.00%
+3    100aa, >d... at 90.00%
+";
+        let clusters: Vec<Cluster> = from_str(data).collect::<Result<_>>().unwrap();
+        let (a, b) = (&clusters[0], &clusters[1]);
+        assert_eq!(a.overlap_count(b), 2);
+        assert_eq!(b.overlap_count(a), 2);
+        // the repeated d counts once
+        assert_eq!(a.jaccard_similarity(b), 0.5);
+        assert_eq!(a.jaccard_similarity(a), 1.0);
+
+        let empty: Cluster = std::iter::empty().collect();
+        assert_eq!(a.jaccard_similarity(&empty), 0.0);
+        assert_eq!(empty.jaccard_similarity(&empty), 1.0);
+        assert_eq!(empty.overlap_count(a), 0);
+    }
+
+    #[test]
+    fn test_sort_sequences() {
+        let data = ">Cluster 0
+0    4481aa, >a... at 99.89%
+1    7126aa, >b... at 66.94%
+2    7182aa, >c... *
+3    7119aa, >d... at 67.17%
+";
+        let mut cluster = from_str(data).next().unwrap().unwrap();
+        let ids = |c: &Cluster| c.iter().map(|s| s.id().to_string()).collect::<Vec<_>>();
+
+        cluster.sort_by_length_desc(RepresentativePlacement::Sorted);
+        assert_eq!(ids(&cluster), ["c", "b", "d", "a"]);
+        cluster.sort_by_length_desc(RepresentativePlacement::Last);
+        assert_eq!(ids(&cluster), ["b", "d", "a", "c"]);
+        cluster.sort_by_length(SortOrder::Ascending, RepresentativePlacement::Sorted);
+        assert_eq!(ids(&cluster), ["a", "d", "b", "c"]);
+        cluster.sort_by_length(SortOrder::Ascending, RepresentativePlacement::First);
+        assert_eq!(ids(&cluster), ["c", "a", "d", "b"]);
+
+        cluster.sort_by_identity_desc(RepresentativePlacement::Sorted);
+        assert_eq!(ids(&cluster), ["a", "d", "b", "c"]);
+        cluster.sort_by_identity_desc(RepresentativePlacement::First);
+        assert_eq!(ids(&cluster), ["c", "a", "d", "b"]);
+
+        cluster.sort_by_key(|s| s.id().to_string(), RepresentativePlacement::Last);
+        assert_eq!(ids(&cluster), ["a", "b", "d", "c"]);
+
+        // the writer renumbers in the new order
+        let mut output = Vec::new();
+        ClstrWriter::new(&mut output)
+            .write_cluster(&cluster)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\n3    7182aa, >c... *\n"));
+    }
+
+    #[test]
+    fn test_representative_or_longest() {
+        let data = ">Cluster 0
+0    7182aa, >b... at 99.89%
+1    4481aa, >d... at 66.94%
+2    7182aa, >a... at 67.17%
+3    4481aa, >c... *
+>Cluster 1
+";
+        let mut clusters: Vec<Cluster> = from_str(data).collect::<Result<_>>().unwrap();
+        let cluster = &mut clusters[0];
+
+        assert_eq!(cluster.longest().unwrap().id(), "a");
+        assert_eq!(cluster.shortest().unwrap().id(), "c");
+        assert_eq!(cluster.representative_or_longest().unwrap().id(), "c");
+        cluster.clear_representative();
+        assert_eq!(cluster.representative_or_longest().unwrap().id(), "a");
+
+        assert!(clusters[1].representative_or_longest().is_none());
+        assert!(clusters[1].shortest().is_none());
+    }
+
+    #[test]
+    fn test_representatives() {
+        let data = ">Cluster 0
+0    100aa, >a... at 90.00%
+1    200aa, >b... *
+>Cluster 1
+>Cluster 2
+0    100aa, >c... at 90.00%
+1    300aa, >d... at 90.00%
+";
+        let mut reps = representatives(from_str(data));
+        let found: Vec<(usize, String)> = reps
+            .by_ref()
+            .map(|r| r.map(|(id, s)| (id, s.id().to_string())))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(found, vec![(0, "b".to_string()), (2, "d".to_string())]);
+        assert_eq!(reps.skipped(), 1);
+
+        let mut reps = representatives_with(from_str(data), MissingRepresentative::Skip);
+        let found: Vec<usize> = reps.by_ref().map(|r| r.unwrap().0).collect();
+        assert_eq!(found, vec![0]);
+        assert_eq!(reps.skipped(), 2);
+
+        let mut reps = representatives(from_str(">Cluster x\n"));
+        assert!(reps.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_top_n_by() {
+        let data = ">Cluster 5
+0    100aa, >a... *
+>Cluster 1
+0    100aa, >b... *
+1    100aa, >c... at 90.00%
+>Cluster 3
+0    100aa, >d... *
+>Cluster 2
+0    100aa, >e... *
+1    300aa, >f... at 90.00%
+2    100aa, >g... at 90.00%
+";
+        let ids = |clusters: Vec<Cluster>| -> Vec<usize> {
+            clusters.iter().map(Cluster::cluster_id).collect()
+        };
+
+        assert_eq!(
+            ids(top_n_by(from_str(data), 3, Cluster::size).unwrap()),
+            vec![2, 1, 3]
+        );
+        assert_eq!(
+            ids(top_n_by(from_str(data), 9, Cluster::size).unwrap()),
+            vec![2, 1, 3, 5]
+        );
+        assert!(top_n_by(from_str(data), 0, Cluster::size)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            ids(top_n_by(from_str(data), 2, |c| c.longest().map(Sequence::length)).unwrap()),
+            vec![2, 1]
+        );
+        assert!(top_n_by(from_str(">Cluster x\n"), 2, Cluster::size).is_err());
+    }
+
+    #[test]
+    fn test_renumber() {
+        let data = ">Cluster 4
+0    4481aa, >a... *
+>Cluster x
+>Cluster 9
+0    7182aa, >b... *
+";
+        let renumbered: Vec<Result<Cluster>> = renumber(from_str(data)).collect();
+        assert_eq!(renumbered.iter().filter(|c| c.is_err()).count(), 1);
+        let ids: Vec<usize> = renumbered
+            .iter()
+            .filter_map(|c| c.as_ref().ok())
+            .map(Cluster::cluster_id)
+            .collect();
+        assert_eq!(ids, vec![0, 1]);
+
+        let mut mapping = Vec::new();
+        let ids: Vec<usize> = renumber_with_mapping(from_str(data), &mut mapping)
+            .filter_map(|c| c.ok())
+            .map(|c| c.cluster_id())
+            .collect();
+        assert_eq!(ids, vec![0, 1]);
+        assert_eq!(mapping, vec![(4, 0), (9, 1)]);
+    }
+
+    #[test]
+    fn test_cluster_iterator_ext() {
+        let data = ">Cluster 0
+0    4481aa, >a... at 99.89%
+1    7182aa, >b... *
+>Cluster 1
+0    4471aa, >c... *
+>Cluster x
+>Cluster 2
+0    100aa, >d... at 99.89%
+1    200aa, >e... *
+2    300aa, >f... at 99.89%
+";
+        // the bad header is reported before the cluster that precedes it
+        let ids = |iter: &mut dyn Iterator<Item = Result<Cluster>>| {
+            iter.map(|c| c.map(|c| c.cluster_id()).map_err(|_| "error"))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ids(&mut from_str(data).min_size(2)),
+            vec![Ok(0), Err("error"), Ok(2)]
+        );
+        assert_eq!(
+            ids(&mut from_str(data).max_size(1)),
+            vec![Err("error"), Ok(1)]
+        );
+        assert_eq!(
+            ids(&mut from_str(data).singletons()),
+            vec![Err("error"), Ok(1)]
+        );
+        assert_eq!(
+            ids(&mut from_str(data).non_singletons()),
+            vec![Ok(0), Err("error"), Ok(2)]
+        );
+        let singletons: Vec<Cluster> =
+            from_str(">Cluster 0\n0    100aa, >a... *\n>Cluster 1\n0    100aa, >b... at 90.00%\n")
+                .collect::<Result<_>>()
+                .unwrap();
+        assert!(singletons.iter().all(Cluster::is_singleton));
+        assert!(singletons[0].is_plain_singleton());
+        assert!(!singletons[1].is_plain_singleton());
+        assert_eq!(
+            ids(&mut from_str(data).filter_size_range(2, Some(2))),
+            vec![Ok(0), Err("error")]
+        );
+        assert_eq!(
+            ids(&mut from_str(data).filter_size_range(1, None)),
+            vec![Ok(0), Err("error"), Ok(1), Ok(2)]
+        );
+        assert_eq!(
+            ids(&mut from_str(data).filter_size_range(3, Some(2))),
+            vec![Err("error")]
+        );
+
+        let good = data.replace(">Cluster x\n", "");
+        let (big, small) = from_str(&good).partition_by(|c| c.size() > 1).unwrap();
+        assert_eq!(
+            big.iter().map(Cluster::cluster_id).collect::<Vec<_>>(),
+            vec![0, 2]
+        );
+        assert_eq!(
+            small.iter().map(Cluster::cluster_id).collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert!(from_str(data).partition_by(|_| true).is_err());
+
+        let mut a = ClstrWriter::new(Vec::new());
+        let mut b = ClstrWriter::new(Vec::new());
+        from_str(&good)
+            .partition_write(|c| c.size() > 1, &mut a, &mut b)
+            .unwrap();
+        let b = String::from_utf8(b.into_inner().unwrap()).unwrap();
+        assert_eq!(b, ">Cluster 1\n0    4471aa, >c... *\n");
+        assert_eq!(from_str(a.into_inner().unwrap()).count(), 2);
+        assert_eq!(
+            ids(&mut from_str(data).min_size(2).min_rep_length(1000)),
+            vec![Ok(0), Err("error")]
+        );
+        let wanted = HashSet::from(["c", "f"]);
+        assert_eq!(
+            ids(&mut from_str(data).ids_in(&wanted)),
+            vec![Err("error"), Ok(1), Ok(2)]
+        );
+    }
+
+    #[test]
+    fn test_progress() {
+        let mut data = String::new();
+        for c in 0..25 {
+            data.push_str(&format!(">Cluster {c}\n0    100aa, >seq_{c}... *\n"));
+        }
+
+        let reported = std::cell::RefCell::new(Vec::new());
+        let clusters: Vec<Cluster> = from_str(&data)
+            .with_progress(|n| reported.borrow_mut().push(n))
+            .with_progress_interval(10)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(clusters.len(), 25);
+        assert_eq!(reported.into_inner(), vec![10, 20]);
+
+        let reported = std::cell::Cell::new(0);
+        from_str(&data)
+            .with_progress(|n| reported.set(n))
+            .for_each(drop);
+        assert_eq!(reported.get(), 0);
+    }
+
+    #[test]
+    fn test_header_note() {
+        let data = ">Cluster 17 phage-like
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... *
+>Cluster 18
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... *
+";
+        let clusters: Vec<Cluster> = from_str(data).collect::<Result<_>>().unwrap();
+        assert_eq!(clusters[0].cluster_id(), 17);
+        assert_eq!(clusters[0].header_note(), Some("phage-like"));
+        assert_eq!(clusters[1].header_note(), None);
+
+        let mut plain = Vec::new();
+        let mut noted = Vec::new();
+        let mut plain_writer = ClstrWriter::new(&mut plain);
+        let mut noted_writer = ClstrWriter::new(&mut noted).with_header_notes(true);
+        for cluster in &clusters {
+            plain_writer.write_cluster(cluster).unwrap();
+            noted_writer.write_cluster(cluster).unwrap();
+        }
+        assert!(String::from_utf8(plain)
+            .unwrap()
+            .starts_with(">Cluster 17\n"));
+        assert_eq!(String::from_utf8(noted).unwrap(), data);
+    }
+
+    #[test]
+    fn test_into_sequences() {
+        let data = ">Cluster 4
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+>Cluster 8
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... *
+";
+        let records: Vec<(usize, Sequence)> = from_str(data)
+            .into_sequences()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].0, 4);
+        assert_eq!(records[0].1.identity(), Some(99.89));
+        assert!(!records[0].1.is_representative());
+        assert_eq!(records[1].0, 4);
+        assert!(records[1].1.is_representative());
+        assert_eq!(records[2].0, 8);
+        assert_eq!(records[2].1.id(), "sp|P0C6U3|R1A_CVHN1");
+    }
+
+    #[test]
+    fn test_cluster_iteration() {
+        let data = ">Cluster 3
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7126aa, >sp|P0C6W1|R1AB_BC133... at 66.94%
+2    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+";
+        let mut cluster = from_str(data).next().unwrap().unwrap();
+
+        let mut total = 0;
+        for seq in &cluster {
+            total += seq.length();
+        }
+        assert_eq!(total, 4481 + 7126 + 7182);
+        assert_eq!(cluster.iter().filter(|s| s.identity().is_some()).count(), 2);
+
+        for seq in &mut cluster {
+            seq.is_representative = false;
+        }
+        cluster.iter_mut().last().unwrap().is_representative = true;
+        assert_eq!(cluster.get_representative().unwrap().length(), 7182);
+
+        let long: Vec<Sequence> = cluster.into_iter().filter(|s| s.length() > 5000).collect();
+        assert_eq!(long.len(), 2);
+
+        let mut collected: Cluster = long.into_iter().collect();
+        assert_eq!(collected.cluster_id(), 0);
+        assert_eq!(collected.size(), 2);
+
+        let more = from_str(data).next().unwrap().unwrap();
+        collected.extend(more);
+        assert_eq!(collected.size(), 5);
+    }
+
+    #[test]
+    fn test_find_sequence() {
+        let data = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7126aa, >sp|P0C6W1|R1AB_BC133... at 66.94%
+2    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+";
+        let cluster = from_str(data).next().unwrap().unwrap();
+
+        assert_eq!(
+            cluster
+                .find_sequence("sp|P0C6W1|R1AB_BC133")
+                .map(Sequence::length),
+            Some(7126)
+        );
+        assert!(cluster.contains_id("sp|P0C6W4|R1AB_BCHK5"));
+        assert!(!cluster.contains_id("sp|P0C6W4"));
+    }
+
+    #[test]
+    fn test_maybe_truncated() {
+        let data = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    120aa, >short_id... *
+";
+        let cluster = from_str(data).next().unwrap().unwrap();
+        assert!(cluster.sequences()[0].maybe_truncated());
+        assert!(!cluster.sequences()[1].maybe_truncated());
+
+        let options = ParseOptions {
+            description_length: 0,
+            ..Default::default()
+        };
+        let cluster = ClstrParser::with_options(data.as_bytes(), options)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert!(!cluster.sequences()[0].maybe_truncated());
+
+        // with `-d 30`, names are cut to 29 characters
+        let data = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5_ABCDEFGHI... at 99.89%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5_ABCDEF... *
+";
+        for (d, flagged) in [
+            (20, [true, true]),
+            (30, [true, false]),
+            (31, [false, false]),
+        ] {
+            let options = ParseOptions {
+                description_length: d,
+                ..Default::default()
+            };
+            let cluster = ClstrParser::with_options(data.as_bytes(), options)
+                .next()
+                .unwrap()
+                .unwrap();
+            let got: Vec<bool> = cluster.iter().map(Sequence::maybe_truncated).collect();
+            assert_eq!(got, flagged, "-d {d}");
+        }
+    }
+
+    #[test]
+    fn test_filter_and_map_clusters() {
+        let data = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+>Cluster 1
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... *
+>Cluster 2
+0    4441aa, >sp|P0C6U4|R1A_CVHN2... *
+1    4421aa, >sp|P0C6U5|R1A_CVHN5... at 81.52%
+>Cluster 3
+0    4421bp, >sp|P0C6U6|R1A_CVHN6... *
+";
+
+        let mut filtered = from_str(data).filter_clusters(|c| c.size() >= 2);
+        assert_eq!(filtered.next().unwrap().unwrap().cluster_id(), 0);
+        assert_eq!(filtered.next().unwrap().unwrap().cluster_id(), 2);
+        assert!(filtered.next().unwrap().is_err());
+
+        let ids: Vec<usize> = from_str(data)
+            .map_clusters(|c| c.cluster_id())
+            .take(3)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(ids, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_parse_options() {
+        use std::sync::{Arc, Mutex};
+
+        let data = b">Cluster 5
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7126bp, >sp|P0C6W1|R1AB_BC133... at 66.94%
+2    122nt, >::SUPER_5:20757114-20757236... at 97.54%
+3    7182aa, >sp|P0C6W4|R1AB_BCHK5... at 101.00%
+4    7182aa, >sp|P0C6W3|R1AB_BCHK4... *
+>Cluster 9
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... *
+" as &[u8];
+
+        // strict by default
+        let strict: Result<Vec<Cluster>> = ClstrParser::new(data).collect();
+        assert!(strict.is_err());
+
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&warnings);
+        let options = ParseOptions {
+            sequence_type: SequenceType::Amino,
+            strict: false,
+            preserve_cluster_ids: false,
+            on_warning: Some(Box::new(move |line, msg| {
+                sink.lock()
+                    .unwrap()
+                    .push((line.to_string(), msg.to_string()));
+            })),
+            ..ParseOptions::default()
+        };
+
+        let clusters: Vec<Cluster> = ClstrParser::with_options(data, options)
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].cluster_id(), 0);
+        assert_eq!(clusters[1].cluster_id(), 1);
+        assert_eq!(clusters[0].size(), 2);
+        assert_eq!(clusters[0].sequences()[1].id(), "sp|P0C6W3|R1AB_BCHK4");
+
+        let warnings = warnings.lock().unwrap();
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].0.contains("7126bp"));
+        assert!(warnings[1].1.contains("expected an amino acid sequence"));
+        assert!(warnings[2].1.contains("exceeds the maximum"));
+    }
+
+    #[test]
+    fn test_parse_options_min_identity() {
+        let data = ">Cluster 0\n0    4481aa, >a... *\n1    4471aa, >b... at -3.50%\n";
+
+        let err = from_str(data).next().unwrap().unwrap_err();
+        assert!(
+            matches!(err.kind(), ErrorKind::Parse { line: 3, msg, .. } if msg.contains("below the minimum of 0%")),
+            "{err}"
+        );
+
+        let options = ParseOptions {
+            min_identity_value: f32::NEG_INFINITY,
+            ..ParseOptions::default()
+        };
+        let cluster = ClstrParser::with_options(data.as_bytes(), options)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(cluster.sequences()[1].identity(), Some(-3.5));
+    }
+
+    #[test]
+    fn test_parser_warnings() {
+        let data = b">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+slurmstepd: error: job 1234 epilogue failed
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+>Cluster 1
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... *
+" as &[u8];
+        let options = ParseOptions {
+            strict: false,
+            ..ParseOptions::default()
+        };
+
+        let mut parser = ClstrParser::with_options(data, options);
+        let clusters: Vec<Cluster> = parser.by_ref().collect::<Result<_>>().unwrap();
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].size(), 2);
+
+        let warnings = parser.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 3);
+        assert_eq!(
+            warnings[0].text,
+            "slurmstepd: error: job 1234 epilogue failed"
+        );
+        assert!(warnings[0].to_string().starts_with("line 3: "));
+
+        // nothing is recorded in strict mode, which fails instead
+        let mut parser = ClstrParser::new(data);
+        assert!(parser.by_ref().any(|c| c.is_err()));
+        assert!(parser.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_check_indices() {
+        let data = b">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+2    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+>Cluster 1
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... *
+" as &[u8];
+
+        // unchecked by default
+        assert!(ClstrParser::new(data).all(|c| c.is_ok()));
+
+        let options = ParseOptions {
+            check_indices: true,
+            ..ParseOptions::default()
+        };
+        let err = ClstrParser::with_options(data, options)
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Parse { line: 3, .. }));
+        assert!(err
+            .to_string()
+            .contains("index 2 does not match position 1"));
+
+        let options = ParseOptions {
+            check_indices: true,
+            strict: false,
+            ..ParseOptions::default()
+        };
+        let mut parser = ClstrParser::with_options(data, options);
+        let clusters: Vec<Cluster> = parser.by_ref().collect::<Result<_>>().unwrap();
+        assert_eq!(clusters[0].size(), 2);
+        assert_eq!(parser.warnings().len(), 1);
+        assert_eq!(parser.warnings()[0].line, 3);
+    }
 
     #[test]
-    fn test_clstr_parsing() {
-        let data = b">Cluster 0
-0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
-1    7126aa, >sp|P0C6W1|R1AB_BC133... at 66.94%
-2    7119aa, >sp|P0C6W3|R1AB_BCHK4... at 67.17%
-3    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
-4    307aa, >sp|Q9WQ77|R1AB_CVRSD... at 76.22%
->Cluster 1
-0    4471aa, >sp|P0C6U3|R1A_CVHN1... at 99.91%
-1    4441aa, >sp|P0C6U4|R1A_CVHN2... at 81.47%
-2    4421aa, >sp|P0C6U5|R1A_CVHN5... at 81.52%
+    fn test_bad_header_policy() {
+        let data = b">Cluster 4
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... *
+>Cluster
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... *
+>cluster 9
+0    4441aa, >sp|P0C6U4|R1A_CVHN2... *
 " as &[u8];
+        let parse = |strict, on_bad_header| {
+            let options = ParseOptions {
+                strict,
+                on_bad_header,
+                ..ParseOptions::default()
+            };
+            ClstrParser::with_options(data, options).collect::<Result<Vec<Cluster>>>()
+        };
+        let ids = |clusters: Vec<Cluster>| -> Vec<usize> {
+            clusters.iter().map(Cluster::cluster_id).collect()
+        };
 
-        let mut parser = ClstrParser::new(data);
+        // both malformed headers fail in strict mode, lowercase included
+        assert!(parse(true, BadHeaderPolicy::Error).is_err());
+        assert!(parse(false, BadHeaderPolicy::Error).is_err());
 
-        let cluster0 = parser.next().unwrap().unwrap();
-        assert_eq!(cluster0.cluster_id(), 0);
-        assert_eq!(cluster0.size(), 5);
-        assert_eq!(cluster0.sequences()[0].id(), "sp|P0C6T5|R1A_BCHK5");
-        assert_eq!(cluster0.sequences()[0].identity(), Some(99.89));
-        assert!(!cluster0.sequences()[0].is_representative());
+        let assigned = parse(false, BadHeaderPolicy::AssignSequential).unwrap();
+        assert_eq!(assigned[1].sequences()[0].id(), "sp|P0C6U3|R1A_CVHN1");
+        assert_eq!(ids(assigned), vec![4, 5, 9]);
+        assert_eq!(
+            ids(parse(true, BadHeaderPolicy::AssignSequential).unwrap()),
+            vec![4, 5, 6]
+        );
 
-        assert_eq!(cluster0.sequences()[3].id(), "sp|P0C6W4|R1AB_BCHK5");
-        assert!(cluster0.sequences()[3].is_representative());
-        assert_eq!(cluster0.sequences()[3].identity(), None);
+        let skipped = parse(false, BadHeaderPolicy::Skip).unwrap();
+        assert_eq!(skipped[0].size(), 1);
+        assert_eq!(ids(skipped), vec![4, 9]);
+        assert_eq!(ids(parse(true, BadHeaderPolicy::Skip).unwrap()), vec![4]);
+    }
 
-        let cluster1 = parser.next().unwrap().unwrap();
-        assert_eq!(cluster1.cluster_id(), 1);
-        assert_eq!(cluster1.size(), 3);
-        assert_eq!(cluster1.sequences()[0].id(), "sp|P0C6U3|R1A_CVHN1");
-        assert_eq!(cluster1.sequences()[0].identity(), Some(99.91));
-        assert!(!cluster1.sequences()[0].is_representative());
+    #[test]
+    fn test_duplicate_cluster_ids() {
+        let chunk = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... *
+>Cluster 1
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... *
+";
+        let data = format!("{chunk}{chunk}");
+        let parse = |policy| {
+            let options = ParseOptions {
+                on_duplicate_id: policy,
+                ..ParseOptions::default()
+            };
+            ClstrParser::with_options(data.as_bytes(), options).collect::<Result<Vec<Cluster>>>()
+        };
+
+        let ids = |clusters: Vec<Cluster>| -> Vec<usize> {
+            clusters.iter().map(Cluster::cluster_id).collect()
+        };
+
+        assert_eq!(
+            ids(parse(DuplicateIdPolicy::Keep).unwrap()),
+            vec![0, 1, 0, 1]
+        );
+        assert_eq!(
+            ids(parse(DuplicateIdPolicy::Renumber).unwrap()),
+            vec![0, 1, 2, 3]
+        );
+
+        let err = parse(DuplicateIdPolicy::Error).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "parse error at line 5: duplicate cluster ID 0"
+        );
+
+        let kept = parse(DuplicateIdPolicy::Keep).unwrap();
+        assert_eq!(ids(renumber_clusters(kept).collect()), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_from_str() {
+        let data = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+";
+
+        let borrowed: Vec<Cluster> = from_str(data).collect::<Result<_>>().unwrap();
+        let owned: Vec<Cluster> = from_str(String::from(data)).collect::<Result<_>>().unwrap();
+
+        assert_eq!(borrowed.len(), 1);
+        assert_eq!(owned.len(), 1);
+        assert_eq!(borrowed[0].size(), 2);
+        assert_eq!(owned[0].sequences()[1].id(), "sp|P0C6W4|R1AB_BCHK5");
+    }
+
+    #[test]
+    fn test_fixtures_parse() {
+        for (name, clusters, sequences) in [
+            ("test.clstr", 2, 8),
+            ("test.top500.clstr", 2, 8),
+            ("test_nt.clstr", 4, 9),
+            ("test_spacing.clstr", 2, 5),
+        ] {
+            let path = format!("{}/data/{name}", env!("CARGO_MANIFEST_DIR"));
+            let parsed: Vec<Cluster> = from_path(&path).unwrap().collect::<Result<_>>().unwrap();
+            assert_eq!(parsed.len(), clusters, "{name}");
+            assert_eq!(
+                parsed.iter().map(Cluster::size).sum::<usize>(),
+                sequences,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_fixture() {
+        // the fixtures in data/ are in the CD-HIT and CD-HIT-EST formats, but
+        // not whole CD-HIT runs: cluster 1 of test.clstr has no representative
+        let parse = |path: &Path| -> Vec<Cluster> {
+            let options = ParseOptions {
+                cd_hit_est: true,
+                ..ParseOptions::default()
+            };
+            ClstrParser::with_options(ClstrReader::from_path(path).unwrap(), options)
+                .collect::<Result<_>>()
+                .unwrap()
+        };
+        for (name, clusters, sequences) in [("test.clstr", 2, 8), ("test_nt.clstr", 4, 9)] {
+            let path = format!("{}/data/{name}", env!("CARGO_MANIFEST_DIR"));
+            let parsed = parse(Path::new(&path));
+            assert_eq!(parsed.len(), clusters, "{name}");
+            assert_eq!(
+                parsed.iter().map(Cluster::size).sum::<usize>(),
+                sequences,
+                "{name}"
+            );
+
+            let copy = temp_path(&format!("fixture_{name}"));
+            let mut writer = to_path(&copy).unwrap();
+            for cluster in &parsed {
+                writer.write_cluster(cluster).unwrap();
+            }
+            writer.flush().unwrap();
+            drop(writer);
+
+            assert_eq!(parse(&copy), parsed, "{name}");
+            std::fs::remove_file(&copy).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_irregular_spacing() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_spacing.clstr");
+        let clusters: Vec<Cluster> = from_path(path).unwrap().collect::<Result<_>>().unwrap();
+
+        let first = clusters[0].sequences();
+        assert_eq!(first[0].id(), "sp|P0C6T5|R1A_BCHK5");
+        assert_eq!(first[1].id(), "sp|P0C6W1|R1AB_BC133");
+        assert_eq!(first[1].length(), 7126);
+        assert_eq!(first[1].identity(), Some(66.94));
+        assert_eq!(first[2].id(), "sp|P0C6W4|R1AB_BCHK5");
+        assert!(first[2].is_representative());
+
+        let second = clusters[1].sequences();
+        assert_eq!(second[0].id(), "sp|P0C6U3|R1A_CVHN1");
+        assert!(second[0].is_representative());
+        assert_eq!(second[1].identity(), Some(81.47));
+
+        assert!(from_str(">Cluster 0\n0    4481aa, no id\n")
+            .next()
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn test_id_ending_in_asterisk() {
+        let data = ">Cluster 0
+0    121aa, >ORF7a*... at 97.52%
+1    121aa, >ORF7b*...
+2    121aa, >ORF7c... *
+";
+        let cluster = from_str(data).next().unwrap().unwrap();
+        let representatives: Vec<&str> = cluster
+            .sequences()
+            .iter()
+            .filter(|s| s.is_representative())
+            .map(Sequence::id)
+            .collect();
+        assert_eq!(representatives, vec!["ORF7c"]);
+        assert_eq!(cluster.sequences()[1].id(), "ORF7b*");
+        assert!(from_str(data).validate().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_id_containing_at() {
+        let data = ">Cluster 0
+0    312nt, >isolate at station_4|k141_22... at 98.10%
+1    340nt, >isolate at station_9|k141_7... *
+";
+        let cluster = from_str(data).next().unwrap().unwrap();
+        let member = &cluster.sequences()[0];
+        assert_eq!(member.id(), "isolate at station_4|k141_22");
+        assert_eq!(member.identity(), Some(98.1));
+
+        let representative = cluster.get_representative().unwrap();
+        assert_eq!(representative.id(), "isolate at station_9|k141_7");
+        assert_eq!(representative.identity(), None);
+    }
+
+    #[test]
+    fn test_from_path_gzip() {
+        let data = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test.clstr");
+        let plain: Vec<Cluster> = from_path(data).unwrap().collect::<Result<_>>().unwrap();
+
+        let gz = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test.clstr.gz");
+        let parser = from_path(gz).unwrap();
+        assert!(matches!(parser.reader, ClstrReader::Gzip(_)));
+        let gzipped: Vec<Cluster> = parser.collect::<Result<_>>().unwrap();
+
+        assert_eq!(plain.len(), 2);
+        assert_eq!(gzipped.len(), 2);
+        for (a, b) in plain.iter().zip(gzipped.iter()) {
+            assert_eq!(a.cluster_id(), b.cluster_id());
+            assert_eq!(a.size(), b.size());
+        }
+    }
+
+    #[test]
+    fn test_to_path_gzip_roundtrip() {
+        let data = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test.clstr");
+        let clusters: Vec<Cluster> = from_path(data).unwrap().collect::<Result<_>>().unwrap();
+
+        let path = temp_path("roundtrip.clstr.gz");
+        {
+            let mut writer = to_path(&path).unwrap();
+            for cluster in &clusters {
+                writer.write_cluster(cluster).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+
+        assert!(std::fs::read(&path).unwrap().starts_with(GZIP_MAGIC));
+        let reread: Vec<Cluster> = from_path(&path).unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(reread.len(), clusters.len());
+        assert_eq!(reread[1].size(), clusters[1].size());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_from_path_compression_formats() {
+        let data = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test.clstr");
+        let plain: Vec<Cluster> = from_path(data).unwrap().collect::<Result<_>>().unwrap();
+
+        for ext in ["bz2", "xz", "zst"] {
+            let path = format!("{data}.{ext}");
+            let parser = from_path(&path).unwrap();
+            match (ext, &parser.reader) {
+                ("bz2", ClstrReader::Bzip2(_))
+                | ("xz", ClstrReader::Xz(_))
+                | ("zst", ClstrReader::Zstd(_)) => {}
+                _ => panic!("{path} was not detected as {ext}"),
+            }
+
+            let clusters: Vec<Cluster> = parser.collect::<Result<_>>().unwrap();
+            assert_eq!(clusters.len(), plain.len());
+            for (a, b) in plain.iter().zip(clusters.iter()) {
+                assert_eq!(a.cluster_id(), b.cluster_id());
+                assert_eq!(a.size(), b.size());
+            }
+        }
+
+        // detection does not depend on the file name
+        let renamed = temp_path("compressed");
+        std::fs::copy(format!("{data}.zst"), &renamed).unwrap();
+        let clusters: Vec<Cluster> = from_path(&renamed).unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(clusters.len(), plain.len());
+        std::fs::remove_file(&renamed).unwrap();
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_from_mmap() {
+        let data = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_nt.clstr");
+        let clusters: Vec<Cluster> = from_mmap(data).unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(clusters.len(), 4);
+        assert_eq!(clusters[3].size(), 6);
+
+        let empty = temp_path("mmap_empty.clstr");
+        std::fs::write(&empty, "").unwrap();
+        assert!(from_mmap(&empty).unwrap().next().is_none());
+
+        let unterminated = temp_path("mmap_unterminated.clstr");
+        std::fs::write(
+            &unterminated,
+            ">Cluster 0\n0    4481aa, >sp|P0C6T5|R1A_BCHK5... *",
+        )
+        .unwrap();
+        let clusters: Vec<Cluster> = from_mmap(&unterminated)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(clusters.len(), 1);
+        assert!(clusters[0].sequences()[0].is_representative());
+
+        std::fs::remove_file(&empty).unwrap();
+        std::fs::remove_file(&unterminated).unwrap();
+    }
+
+    #[test]
+    fn test_from_path_stdin_and_missing() {
+        let parser = from_path("-").unwrap();
+        assert!(matches!(parser.reader, ClstrReader::Stdin(_)));
+
+        let err = from_path("no/such/file.clstr").err().unwrap();
+        assert!(matches!(err.kind(), ErrorKind::Io(_)));
+        assert!(err.to_string().contains("no/such/file.clstr"));
+    }
+
+    #[test]
+    fn test_from_path_corrupt_gzip() {
+        let path = temp_path("corrupt.clstr.gz");
+        std::fs::write(&path, [0x1f, 0x8b, 0x08, 0x00, 0xde, 0xad, 0xbe, 0xef]).unwrap();
+
+        let err = from_path(&path).unwrap().next().unwrap().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Io(_)));
+
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
@@ -428,7 +4298,9 @@ mod tests {
             unit: LengthUnit::Aa,
             id: "sp|P0C6T5|R1A_BCHK5".to_string(),
             identity: Some(99.89),
+            strand: None,
             is_representative: false,
+            maybe_truncated: true,
         };
 
         let sequence2 = Sequence {
@@ -436,12 +4308,16 @@ mod tests {
             unit: LengthUnit::Aa,
             id: "sp|P0C6W4|R1AB_BCHK5".to_string(),
             identity: None,
+            strand: None,
             is_representative: true,
+            maybe_truncated: true,
         };
 
         let cluster = Cluster {
             cluster_id: 0,
             sequences: vec![sequence1, sequence2],
+            header_note: None,
+            id_index: IdIndex::default(),
         };
 
         let mut output = Cursor::new(Vec::new());
@@ -453,6 +4329,430 @@ mod tests {
         assert_eq!(output_str, ">Cluster 0\n0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%\n1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *\n");
     }
 
+    #[test]
+    fn test_writer_units() {
+        let aa = from_str(">Cluster 0\n0    4481aa, >sp|P0C6T5|R1A_BCHK5... *\n")
+            .next()
+            .unwrap()
+            .unwrap();
+        let nt = from_str(">Cluster 1\n0    201nt, >::SUPER_5:16491930-16492131... *\n")
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(aa.sequences()[0].unit(), LengthUnit::Aa);
+        assert_eq!(nt.sequences()[0].unit(), LengthUnit::Nt);
+
+        let mut output = Vec::new();
+        let mut writer = ClstrWriter::new(&mut output);
+        writer.write_cluster(&aa).unwrap();
+        let err = writer.write_cluster(&nt).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "writing cluster 1 - expected aa sequences, found nt"
+        );
+        assert!(ClstrWriter::new(Vec::new())
+            .with_unit(LengthUnit::Nt)
+            .write_cluster(&aa)
+            .is_err());
+
+        let mut output = Vec::new();
+        let mut writer = ClstrWriter::new(&mut output).allow_mixed_units(true);
+        writer.write_cluster(&aa).unwrap();
+        writer.write_cluster(&nt).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("4481aa, "));
+        assert!(output.contains("201nt, "));
+    }
+
+    #[test]
+    fn test_writer_into_inner() {
+        let cluster = from_str(">Cluster 0\n0    4481aa, >sp|P0C6T5|R1A_BCHK5... *\n")
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let mut writer = ClstrWriter::new(BufWriter::new(Vec::new()));
+        writer.write_cluster(&cluster).unwrap();
+        let output = writer.into_inner().unwrap().into_inner().unwrap();
+        assert_eq!(
+            output,
+            b">Cluster 0\n0    4481aa, >sp|P0C6T5|R1A_BCHK5... *\n"
+        );
+
+        // reusing the buffer
+        let len = output.len();
+        let mut writer = ClstrWriter::new(output);
+        writer.write_cluster(&cluster).unwrap();
+        assert_eq!(writer.into_inner().unwrap().len(), 2 * len);
+    }
+
+    #[test]
+    fn test_to_gz_path() {
+        let data = ">Cluster 0\n0    4481aa, >sp|P0C6T5|R1A_BCHK5... *\n";
+        let cluster = from_str(data).next().unwrap().unwrap();
+        let path = temp_path("to_gz_path.clstr.gz");
+
+        for level in [Compression::default(), Compression::best()] {
+            let mut writer = to_gz_path_with_level(&path, level).unwrap();
+            writer.write_cluster(&cluster).unwrap();
+            // the file is complete once finished, before anything is dropped
+            let _encoder = writer.finish().unwrap();
+
+            let mut output = String::new();
+            MultiGzDecoder::new(BufReader::new(File::open(&path).unwrap()))
+                .read_to_string(&mut output)
+                .unwrap();
+            assert_eq!(output, data);
+        }
+
+        let mut writer = to_gz_path(&path).unwrap();
+        writer.write_cluster(&cluster).unwrap();
+        drop(writer.finish().unwrap());
+        let clusters: Vec<Cluster> = from_path(&path).unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(clusters, vec![cluster]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_membership_tsv() {
+        let data = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+>Cluster 1
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... *
+";
+        let clusters: Vec<Cluster> = from_str(data).collect::<Result<_>>().unwrap();
+
+        let mut output = Vec::new();
+        write_membership_tsv(&mut output, &clusters).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "cluster_id\tsequence_id\tlength\tidentity\tis_representative
+0\tsp|P0C6T5|R1A_BCHK5\t4481\t99.89\tfalse
+0\tsp|P0C6W4|R1AB_BCHK5\t7182\tNA\ttrue
+1\tsp|P0C6U3|R1A_CVHN1\t4471\tNA\ttrue
+"
+        );
+    }
+
+    #[test]
+    fn test_edges() {
+        let data = ">Cluster 0
+0    100aa, >a... at 90.00%
+1    200aa, >b... *
+2    100aa, >c...
+>Cluster 1
+0    100aa, >d... *
+>Cluster 2
+0    100aa, >e... at 80.00%
+1    300aa, >f... at 70.00%
+";
+        let clusters: Vec<Cluster> = from_str(data).collect::<Result<_>>().unwrap();
+        let edges: Vec<Edge> = clusters[0].to_edges().collect();
+        assert_eq!(
+            edges,
+            vec![
+                Edge {
+                    rep_id: "b",
+                    member_id: "a",
+                    identity: Some(90.0),
+                },
+                Edge {
+                    rep_id: "b",
+                    member_id: "c",
+                    identity: None,
+                },
+            ]
+        );
+        assert_eq!(clusters[1].to_edges().count(), 0);
+        assert_eq!(clusters[2].to_edges().count(), 0);
+
+        let mut output = Vec::new();
+        let skipped = write_edge_list(
+            from_str(data),
+            &mut output,
+            ',',
+            MissingRepresentative::Skip,
+        )
+        .unwrap();
+        assert_eq!(skipped, 1);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "rep_id,member_id,identity\nb,a,90.00\nb,c,NA\n"
+        );
+
+        let mut output = Vec::new();
+        let skipped = write_edge_list(
+            from_str(data),
+            &mut output,
+            '\t',
+            MissingRepresentative::Longest,
+        )
+        .unwrap();
+        assert_eq!(skipped, 0);
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .ends_with("f\te\t80.00\n"));
+
+        assert!(write_edge_list(
+            from_str(">Cluster x\n"),
+            Vec::new(),
+            '\t',
+            MissingRepresentative::Skip
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_write_membership_table() {
+        let data = ">Cluster 479
+0       122nt, >r1... at -/97.54%
+1       186nt, >r2... at +/95.70%
+2       198nt, >r3... *
+";
+        let parse = || {
+            ClstrParser::with_options(
+                data.as_bytes(),
+                ParseOptions {
+                    cd_hit_est: true,
+                    ..ParseOptions::default()
+                },
+            )
+        };
+
+        let mut output = Vec::new();
+        let options = MembershipOptions {
+            header: false,
+            delimiter: ',',
+            include_representatives: false,
+            strand: true,
+        };
+        write_membership_table(parse(), &mut output, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "479,r1,122,97.54,false,-\n479,r2,186,95.70,false,+\n"
+        );
+
+        let mut output = Vec::new();
+        let options = MembershipOptions {
+            strand: true,
+            ..MembershipOptions::default()
+        };
+        write_membership_table(parse(), &mut output, &options).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output
+            .starts_with("cluster_id\tsequence_id\tlength\tidentity\tis_representative\tstrand\n"));
+        assert!(output.ends_with("479\tr3\t198\tNA\ttrue\tNA\n"));
+
+        let mut output = Vec::new();
+        stream_membership_tsv(parse(), &mut output).unwrap();
+        let mut expected = Vec::new();
+        write_membership_tsv(&mut expected, &parse().collect::<Result<Vec<_>>>().unwrap()).unwrap();
+        assert_eq!(output, expected);
+
+        let bad = write_membership_table(
+            from_str(">Cluster x\n"),
+            Vec::new(),
+            &MembershipOptions::default(),
+        );
+        assert!(bad.is_err());
+    }
+
+    #[test]
+    fn test_membership_map() {
+        let data = ">Cluster 0
+0    4481aa, >a... at 99.89%
+1    7182aa, >b... *
+>Cluster 1
+0    4471aa, >a... *
+";
+        let map = membership_map(from_str(">Cluster 0\n0    7182aa, >b... *\n"), true).unwrap();
+        assert_eq!(
+            map["b"],
+            Membership {
+                cluster_id: 0,
+                is_representative: true,
+                identity: None
+            }
+        );
+
+        // the later cluster wins
+        let map = membership_map(from_str(data), false).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["a"].cluster_id, 1);
+        let err = membership_map(from_str(data), true).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("sequence 'a' found in cluster 0 and again in cluster 1"));
+
+        let mut streamed = Vec::new();
+        stream_membership_tsv(from_str(data), &mut streamed).unwrap();
+        let mut written = Vec::new();
+        let clusters: Vec<Cluster> = from_str(data).collect::<Result<_>>().unwrap();
+        write_membership_tsv(&mut written, &clusters).unwrap();
+        assert_eq!(streamed, written);
+    }
+
+    #[test]
+    fn test_write_clusters() {
+        let data = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... *
+>Cluster 1
+0    7126aa, >sp|P0C6W1|R1AB_BC133... at 66.94%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+";
+        let clusters: Vec<Cluster> = from_str(data).collect::<Result<_>>().unwrap();
+
+        let mut output = Vec::new();
+        ClstrWriter::new(&mut output)
+            .write_clusters(&clusters)
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), data);
+
+        let mut output = Vec::new();
+        ClstrWriter::new(&mut output)
+            .write_clusters_sorted(&clusters, |a, b| b.size().cmp(&a.size()))
+            .unwrap();
+        let ids: Vec<usize> = from_str(&String::from_utf8(output).unwrap())
+            .map(|c| c.unwrap().cluster_id())
+            .collect();
+        assert_eq!(ids, vec![1, 0]);
+    }
+
+    #[test]
+    fn test_parse_line_and_from_str() {
+        let seq =
+            Sequence::parse_line("1    7126aa, >sp|P0C6W1|R1AB_BC133... at 66.94%\r\n").unwrap();
+        assert_eq!(seq.id(), "sp|P0C6W1|R1AB_BC133");
+        assert_eq!(seq.identity(), Some(66.94));
+        assert!(!seq.is_representative());
+
+        let err = Sequence::parse_line("0    44x81aa, >a... *").unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::Parse {
+                line: 1,
+                column: Some(6),
+                ..
+            }
+        ));
+        let options = ParseOptions {
+            sequence_type: SequenceType::Nucleotide,
+            ..Default::default()
+        };
+        assert!(Sequence::parse_line_with_options("0    4481aa, >a... *", &options).is_err());
+
+        // NaN would make a sequence unequal to itself
+        for identity in ["nan", "NaN", "inf", "-/nan"] {
+            let line = format!("0    100aa, >a... at {identity}%");
+            let err = Sequence::parse_line(&line).unwrap_err();
+            assert!(err.to_string().contains("invalid identity"), "{err}");
+        }
+
+        let data = ">Cluster 1
+0    7126aa, >sp|P0C6W1|R1AB_BC133... at 66.94%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+";
+        let cluster: Cluster = data.parse().unwrap();
+        assert_eq!(cluster, from_str(data).next().unwrap().unwrap());
+        assert_eq!(cluster.sequences()[0], seq);
+
+        assert!("".parse::<Cluster>().is_err());
+        let err = format!("{data}>Cluster 2\n")
+            .parse::<Cluster>()
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Parse { line: 4, .. }));
+        let err = ">Cluster 1\n0    7126xx, >x...\n"
+            .parse::<Cluster>()
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Parse { line: 2, .. }));
+    }
+
+    #[test]
+    fn test_cluster_ord() {
+        let data = ">Cluster 0
+0    4481aa, >a... *
+>Cluster 1
+0    7126aa, >b... at 66.94%
+1    7182aa, >c... *
+>Cluster 2
+0    4471aa, >d... *
+";
+        let mut clusters: Vec<Cluster> = from_str(data).collect::<Result<_>>().unwrap();
+        let mut heap: std::collections::BinaryHeap<Cluster> = clusters.iter().cloned().collect();
+        assert_eq!(heap.pop().unwrap().cluster_id(), 1);
+
+        clusters.sort();
+        let ids: Vec<usize> = clusters.iter().map(Cluster::cluster_id).collect();
+        assert_eq!(ids, vec![0, 2, 1]);
+
+        // equal clusters, and only those, compare equal
+        let mut other = clusters[0].clone();
+        assert_eq!(clusters[0].cmp(&other), std::cmp::Ordering::Equal);
+        other.sequences_mut()[0].set_length(1);
+        assert_ne!(clusters[0].cmp(&other), std::cmp::Ordering::Equal);
+
+        let zero: Cluster = ">Cluster 0\n0    100aa, >a... at 0.00%\n".parse().unwrap();
+        let negative_zero: Cluster = ">Cluster 0\n0    100aa, >a... at -0.00%\n".parse().unwrap();
+        assert_eq!(zero, negative_zero);
+        assert_eq!(zero.cmp(&negative_zero), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn test_display() {
+        let data = ">Cluster 1
+0    7126nt, >::SUPER_1:1-7127... at -/66.94%
+1    7182nt, >::SUPER_2:1-7183... *
+";
+        let options = ParseOptions {
+            cd_hit_est: true,
+            ..Default::default()
+        };
+        let cluster = ClstrParser::with_options(data.as_bytes(), options)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(cluster.to_string(), data);
+        assert_eq!(cluster.to_clstr_string(), data);
+        assert_eq!(
+            cluster.sequences()[0].to_string(),
+            "7126nt, >::SUPER_1:1-7127... at -/66.94%"
+        );
+
+        let mut output = Vec::new();
+        ClstrWriter::new(&mut output)
+            .write_cluster(&cluster)
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), cluster.to_string());
+    }
+
+    #[test]
+    fn test_write_matches_cd_hit_output() {
+        // data/test.clstr was written by CD-HIT itself; everything after the
+        // index column should come back byte for byte
+        let fixture =
+            std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/data/test.clstr"))
+                .unwrap();
+
+        let mut output = Vec::new();
+        let mut writer = ClstrWriter::new(&mut output);
+        for cluster in from_str(&fixture) {
+            writer.write_cluster(&cluster.unwrap()).unwrap();
+        }
+        let output = String::from_utf8(output).unwrap();
+
+        let after_index = |line: &str| {
+            line.split_once(char::is_whitespace)
+                .map(|(_, rest)| rest.trim_start().to_string())
+        };
+        assert_eq!(output.lines().count(), fixture.lines().count());
+        for (written, original) in output.lines().zip(fixture.lines()) {
+            assert_eq!(after_index(written), after_index(original));
+        }
+        assert!(output.contains("R1AB_BCHK5... *\n"));
+    }
+
     #[test]
     fn test_nt_identity_weird_tokens() {
         let data = b">Cluster 479
@@ -464,11 +4764,206 @@ mod tests {
         let mut parser = ClstrParser::new(data);
 
         let cluster = parser.next().unwrap().unwrap();
-        assert_eq!(cluster.cluster_id(), 0);
+        assert_eq!(cluster.cluster_id(), 479);
         assert_eq!(cluster.size(), 3);
 
         assert_eq!(cluster.sequences()[0].identity(), Some(97.54));
         assert_eq!(cluster.sequences()[1].identity(), Some(95.70));
         assert_eq!(cluster.sequences()[2].identity(), None);
     }
+
+    #[test]
+    fn test_cd_hit_est_strand() {
+        let data = ">Cluster 479
+0       122nt, >::SUPER_5:20757114-20757236... at -/97.54%
+1       186nt, >::SUPER_4:13803815-13804001... at +/95.70%
+2       198nt, >::SUPER_2:18124787-18124985... *
+";
+
+        // the strand is only kept when asked for
+        let cluster = from_str(data).next().unwrap().unwrap();
+        assert_eq!(cluster.sequences()[0].strand(), None);
+
+        let options = ParseOptions {
+            cd_hit_est: true,
+            ..Default::default()
+        };
+        let cluster = ClstrParser::with_options(data.as_bytes(), options)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(cluster.sequences()[0].strand(), Some(Strand::Reverse));
+        assert_eq!(cluster.sequences()[0].identity(), Some(97.54));
+        assert_eq!(cluster.sequences()[1].strand(), Some(Strand::Forward));
+        assert_eq!(cluster.sequences()[2].strand(), None);
+
+        let mut output = Vec::new();
+        ClstrWriter::new(&mut output)
+            .write_cluster(&cluster)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("... at -/97.54%\n"));
+        assert!(output.contains("... at +/95.70%\n"));
+    }
+
+    #[test]
+    fn test_crlf_and_trailing_whitespace() {
+        let lf = b">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+>Cluster 1
+0    122nt, >::SUPER_5:20757114-20757236... at -/97.54%
+1    198nt, >::SUPER_2:18124787-18124985... *
+" as &[u8];
+        let crlf = b">Cluster 0\r\n0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%\r\n1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *\r\n>Cluster 1 \t\r\n0    122nt, >::SUPER_5:20757114-20757236... at -/97.54%  \r\n1    198nt, >::SUPER_2:18124787-18124985... * \r\n" as &[u8];
+
+        let lf: Vec<Cluster> = ClstrParser::new(lf).collect::<Result<_>>().unwrap();
+        let crlf: Vec<Cluster> = ClstrParser::new(crlf).collect::<Result<_>>().unwrap();
+
+        assert_eq!(lf.len(), 2);
+        assert_eq!(crlf.len(), 2);
+        for (a, b) in lf.iter().zip(crlf.iter()) {
+            assert_eq!(a.cluster_id(), b.cluster_id());
+            assert_eq!(a.size(), b.size());
+            for (x, y) in a.sequences().iter().zip(b.sequences().iter()) {
+                assert_eq!(x.id(), y.id());
+                assert_eq!(x.identity(), y.identity());
+                assert_eq!(x.is_representative(), y.is_representative());
+            }
+        }
+
+        assert!(crlf[0].sequences()[1].is_representative());
+        assert_eq!(crlf[1].sequences()[0].identity(), Some(97.54));
+        assert!(crlf[1].sequences()[1].is_representative());
+    }
+
+    #[test]
+    fn test_invalid_utf8_policy() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_latin1.clstr");
+        let parse = |on_invalid_utf8| {
+            let options = ParseOptions {
+                on_invalid_utf8,
+                ..Default::default()
+            };
+            ClstrParser::with_options(BufReader::new(File::open(path).unwrap()), options)
+                .collect::<Result<Vec<Cluster>>>()
+        };
+
+        let err = parse(InvalidUtf8Policy::Error).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "parse error at line 2, column 28: invalid UTF-8"
+        );
+
+        let lossy = parse(InvalidUtf8Policy::Lossy).unwrap();
+        assert_eq!(lossy.len(), 2);
+        assert_eq!(lossy[0].size(), 2);
+        assert_eq!(lossy[0].sequences()[0].id(), "sp|P0C6T5|Caf\u{fffd}_1");
+        assert_eq!(lossy[0].sequences()[0].identity(), Some(99.89));
+
+        let mut output = Vec::new();
+        ClstrWriter::new(&mut output)
+            .write_cluster(&lossy[0])
+            .unwrap();
+        let reparsed: Vec<Cluster> = from_str(&output).collect::<Result<_>>().unwrap();
+        assert_eq!(
+            reparsed[0].sequences()[0].id(),
+            lossy[0].sequences()[0].id()
+        );
+
+        let skipped = parse(InvalidUtf8Policy::Skip).unwrap();
+        assert_eq!(skipped.len(), 2);
+        assert_eq!(skipped[0].size(), 1);
+        assert!(skipped[0].sequences()[0].is_representative());
+    }
+
+    #[test]
+    fn test_truncation_policy() {
+        let data = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+>Cluster 1
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... *
+1    4441aa, >sp|P0C6U4|R1A_CV";
+        let parse = |on_truncation| {
+            let options = ParseOptions {
+                strict: false,
+                on_truncation,
+                ..Default::default()
+            };
+            ClstrParser::with_options(data.as_bytes(), options).collect::<Result<Vec<Cluster>>>()
+        };
+
+        let err = parse(TruncationPolicy::Error).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "parse error at line 6: file is truncated, the last line has no newline"
+        );
+
+        let kept = parse(TruncationPolicy::WarnAndKeep).unwrap();
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[1].size(), 2);
+        assert_eq!(kept[1].sequences()[1].id(), "sp|P0C6U4|R1A_CV");
+
+        let dropped = parse(TruncationPolicy::Drop).unwrap();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].cluster_id(), 0);
+
+        // a cut header drops only itself
+        let options = ParseOptions {
+            on_truncation: TruncationPolicy::Drop,
+            ..Default::default()
+        };
+        let cut_header = ">Cluster 0\n0\t100aa, >a... *\n>Cluster 1";
+        let clusters: Vec<Cluster> = ClstrParser::with_options(cut_header.as_bytes(), options)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].size(), 1);
+
+        let mut parser = crate::from_str(data);
+        assert!(!parser.is_truncated());
+        assert_eq!(parser.by_ref().count(), 2);
+        assert!(parser.is_truncated());
+        let mut parser = crate::from_str(">Cluster 0\n0    100aa, >a... *\n");
+        assert_eq!(parser.by_ref().count(), 1);
+        assert!(!parser.is_truncated());
+
+        // a complete file is untouched
+        let complete = format!("{data}...\n");
+        let options = ParseOptions {
+            on_truncation: TruncationPolicy::Error,
+            ..Default::default()
+        };
+        let clusters: Vec<Cluster> = ClstrParser::with_options(complete.as_bytes(), options)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(clusters.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let data = b">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7126bp, >sp|P0C6W1|R1AB_BC133... at 66.94%
+" as &[u8];
+
+        let mut parser = ClstrParser::new(data);
+        let err = parser.next().unwrap().unwrap_err();
+
+        match err.kind() {
+            ErrorKind::Parse { line, column, msg } => {
+                assert_eq!(*line, 3);
+                assert_eq!(*column, Some(6));
+                assert_eq!(msg, "invalid length format '7126bp,'");
+            }
+            other => panic!("unexpected error kind {other:?}"),
+        }
+        assert_eq!(
+            err.to_string(),
+            "parse error at line 3, column 6: invalid length format '7126bp,'"
+        );
+        assert_eq!(parser.line_number(), 3);
+        assert_eq!(parser.byte_offset(), 58);
+    }
 }
@@ -7,13 +7,30 @@
 // - `filtern`: write clusters with at least N records to a new file.
 // - `tofasta`: generate multiple fasta files given an input cluster file.
 // - `stats`: get statistics on a CD-HIT cluster file.
+// - `cat`: concatenate cluster files, renumbering the clusters.
+// - `extract`: write a single cluster, found by its ID.
+// - `compare`: report which sequences moved between clusters of two files.
+// - `split`: partition a cluster file into N parts.
+// - `sample`: randomly draw N clusters.
+// - `filterid`: write the clusters containing any of the given sequence IDs.
+// - `export`: write a table of cluster membership, one sequence per row.
+// - `validate`: check a cluster file for structural problems.
+// - `rename`: replace sequence IDs using a table of old and new IDs.
 
-use std::{collections::HashMap, fs::File, path::PathBuf};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs::File,
+    path::{Path, PathBuf},
+};
 
 use bio::io::fasta;
 use clap::{crate_version, value_parser, Arg, ArgAction, ArgMatches, Command};
-use clstr::{Cluster, Result as ClstrResult};
+use clstr::{
+    ClstrFile, ClstrParser, ClstrReader, ClstrSummary, ClstrWriter, Cluster, ClusterIteratorExt,
+    Finish, ParseOptions, Result as ClstrResult, TruncationPolicy,
+};
 use flate2::read::GzDecoder;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::io::{BufReader, Read, Write};
 
 fn parse_args() -> ArgMatches {
@@ -29,7 +46,7 @@ fn parse_args() -> ArgMatches {
                 .about("Get statistics on a CD-HIT cluster file.")
                 .arg(
                     Arg::new("FILE")
-                        .help("The input file in `.clstr` format.")
+                        .help("The input file in `.clstr` format, or `-` for stdin.")
                         .id("FILE")
                         .value_parser(value_parser!(PathBuf))
                         .required(true)
@@ -43,13 +60,70 @@ fn parse_args() -> ArgMatches {
                         .long("table")
                         .action(ArgAction::SetTrue)
                 )
+                .arg(
+                    Arg::new("identities")
+                        .help("Print each cluster with its size and the min, mean and max identity to its representative")
+                        .id("identities")
+                        .short('i')
+                        .long("identities")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("table")
+                )
+                .arg(
+                    Arg::new("validate")
+                        .help("Check the file for structural problems, such as clusters without a representative, and print them")
+                        .id("validate")
+                        .long("validate")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["table", "identities"])
+                )
+                .arg(
+                    Arg::new("singletons")
+                        .help("Also print the number of singleton clusters, their fraction of all clusters and the fraction of sequences in them")
+                        .id("singletons")
+                        .long("singletons")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["table", "identities", "validate"])
+                )
+                .arg(
+                    Arg::new("lengths")
+                        .help("Also print the average summed sequence length per cluster")
+                        .id("lengths")
+                        .long("lengths")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["table", "identities", "validate"])
+                )
+                .arg(
+                    Arg::new("json")
+                        .help("Print the totals as a JSON object instead of a TSV")
+                        .id("json")
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["table", "identities", "validate", "percentiles", "histogram", "singletons", "lengths"])
+                )
+                .arg(
+                    Arg::new("percentiles")
+                        .help("Print the 5th to 99th percentiles of cluster sizes")
+                        .id("percentiles")
+                        .long("percentiles")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["table", "identities", "validate"])
+                )
+                .arg(
+                    Arg::new("histogram")
+                        .help("Print a histogram of cluster sizes, in buckets of powers of 2")
+                        .id("histogram")
+                        .long("histogram")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["table", "identities", "validate"])
+                )
         )
         .subcommand(
             Command::new("topn")
                 .about("Write the top N clusters to a new file.")
                 .arg(
                     Arg::new("FILE")
-                        .help("The input file in `.clstr` format.")
+                        .help("The input file in `.clstr` format, or `-` for stdin.")
                         .id("FILE")
                         .value_parser(value_parser!(PathBuf))
                         .required(true)
@@ -57,21 +131,30 @@ fn parse_args() -> ArgMatches {
                 )
                 .arg(
                     Arg::new("cluster-number")
-                        .help("The number of top clusters to write to the output file.")
+                        .help("The number of top clusters to write to the output file, or with `--by-sequences` the number of sequences.")
                         .id("cluster-number")
                         .short('n')
                         .long("cluster-number")
                         .num_args(1)
                         .value_parser(value_parser!(usize))
                         .default_value("500"),
-                ),
+                )
+                .arg(
+                    Arg::new("by-sequences")
+                        .help("Take the largest clusters until they hold at least N sequences in total, rather than N clusters.")
+                        .id("by-sequences")
+                        .long("by-sequences")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(mapping_arg())
+                .arg(progress_arg()),
         )
         .subcommand(
             Command::new("filtern")
                 .about("Write clusters with at least N records to a new file.")
                 .arg(
                     Arg::new("FILE")
-                        .help("The input file in `.clstr` format.")
+                        .help("The input file in `.clstr` format, or `-` for stdin.")
                         .id("FILE")
                         .value_parser(value_parser!(PathBuf))
                         .required(true)
@@ -86,14 +169,30 @@ fn parse_args() -> ArgMatches {
                         .num_args(1)
                         .value_parser(value_parser!(usize))
                         .default_value("20"),
-                ),
+                )
+                .arg(
+                    Arg::new("max")
+                        .help("The maximum number of sequences in a cluster for it to be written to the output file.")
+                        .id("max")
+                        .long("max")
+                        .num_args(1)
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("complement")
+                        .help("Also write the clusters that fail the filter to a second file, in the same pass.")
+                        .id("complement")
+                        .long("complement")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(mapping_arg()),
         )
         .subcommand(
             Command::new("tofasta")
                 .about("Generate multiple fasta files given an input cluster file.")
                 .arg(
                     Arg::new("FILE")
-                        .help("The input file in `.clstr` format.")
+                        .help("The input file in `.clstr` format, or `-` for stdin.")
                         .id("FILE")
                         .value_parser(value_parser!(PathBuf))
                         .required(true)
@@ -109,52 +208,540 @@ fn parse_args() -> ArgMatches {
                         .num_args(1)
                         .index(2)
                 )
+                .arg(progress_arg()),
+        )
+        .subcommand(
+            Command::new("cat")
+                .about("Concatenate cluster files, renumbering clusters so that IDs are not repeated.")
+                .arg(
+                    Arg::new("FILES")
+                        .help("The input files in `.clstr` format, or `-` for stdin.")
+                        .id("FILES")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .num_args(1..)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("output")
+                        .help("The file to write to, instead of stdout. Gzipped if it ends in `.gz`.")
+                        .id("output")
+                        .short('o')
+                        .long("output")
+                        .num_args(1)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+        )
+        .subcommand(
+            Command::new("extract")
+                .about("Write the cluster with the given ID, reading no further than it.")
+                .arg(
+                    Arg::new("FILE")
+                        .help("The input file in `.clstr` format, or `-` for stdin.")
+                        .id("FILE")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("cluster-id")
+                        .help("The ID of the cluster to write.")
+                        .id("cluster-id")
+                        .short('c')
+                        .long("cluster-id")
+                        .num_args(1)
+                        .value_parser(value_parser!(usize))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .help("The file to write to, instead of stdout. Gzipped if it ends in `.gz`.")
+                        .id("output")
+                        .short('o')
+                        .long("output")
+                        .num_args(1)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+        )
+        .subcommand(
+            Command::new("compare")
+                .about("Report, as a TSV, the cluster each sequence is in in two cluster files, with a summary of how many moved.")
+                .arg(
+                    Arg::new("FILE1")
+                        .help("The first file in `.clstr` format, or `-` for stdin.")
+                        .id("FILE1")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("FILE2")
+                        .help("The second file in `.clstr` format.")
+                        .id("FILE2")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .index(2),
+                )
+                .arg(
+                    Arg::new("only-changed")
+                        .help("Only print sequences whose cluster differs between the files")
+                        .id("only-changed")
+                        .long("only-changed")
+                        .action(ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            Command::new("split")
+                .about("Split a cluster file into N parts, named `part_000.clstr` onwards, keeping cluster IDs.")
+                .arg(
+                    Arg::new("FILE")
+                        .help("The input file in `.clstr` format, or `-` for stdin.")
+                        .id("FILE")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("count")
+                        .help("The number of parts to write.")
+                        .id("count")
+                        .short('n')
+                        .long("count")
+                        .num_args(1)
+                        .value_parser(value_parser!(u64).range(1..))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("output-dir")
+                        .help("The directory to write the parts to, created if missing.")
+                        .id("output-dir")
+                        .short('o')
+                        .long("output-dir")
+                        .num_args(1)
+                        .value_parser(value_parser!(PathBuf))
+                        .default_value("."),
+                )
+                .arg(
+                    Arg::new("by-clusters")
+                        .help("Put an equal number of clusters in each part (the default)")
+                        .id("by-clusters")
+                        .long("by-clusters")
+                        .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("by-sequences")
+                        .help("Put an equal number of sequences in each part, for when cluster sizes are skewed")
+                        .id("by-sequences")
+                        .long("by-sequences")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("by-clusters")
+                )
+        )
+        .subcommand(
+            Command::new("sample")
+                .about("Randomly draw N clusters, holding no more than N in memory.")
+                .arg(
+                    Arg::new("FILE")
+                        .help("The input file in `.clstr` format, or `-` for stdin.")
+                        .id("FILE")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("count")
+                        .help("The number of clusters to draw.")
+                        .id("count")
+                        .short('n')
+                        .long("count")
+                        .num_args(1)
+                        .value_parser(value_parser!(usize))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .help("The seed for the random number generator, for a reproducible sample.")
+                        .id("seed")
+                        .short('s')
+                        .long("seed")
+                        .num_args(1)
+                        .value_parser(value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("keep-ids")
+                        .help("Keep the cluster IDs of the input, rather than numbering from 0")
+                        .id("keep-ids")
+                        .long("keep-ids")
+                        .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("output")
+                        .help("The file to write to, instead of stdout. Gzipped if it ends in `.gz`.")
+                        .id("output")
+                        .short('o')
+                        .long("output")
+                        .num_args(1)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+        )
+        .subcommand(
+            Command::new("filterid")
+                .about("Write the clusters containing any of the given sequence IDs to stdout.")
+                .arg(
+                    Arg::new("FILE")
+                        .help("The input file in `.clstr` format, or `-` for stdin.")
+                        .id("FILE")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("id")
+                        .help("A sequence ID to look for. May be given more than once.")
+                        .id("id")
+                        .long("id")
+                        .action(ArgAction::Append)
+                        .required_unless_present("ids-file"),
+                )
+                .arg(
+                    Arg::new("ids-file")
+                        .help("A file of sequence IDs to look for, one per line.")
+                        .id("ids-file")
+                        .long("ids-file")
+                        .num_args(1)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("require-representative")
+                        .help("Only match clusters whose representative has one of the IDs")
+                        .id("require-representative")
+                        .long("require-representative")
+                        .action(ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write a TSV with a row per sequence: its cluster ID, ID, length, identity and whether it is the representative.")
+                .arg(
+                    Arg::new("FILE")
+                        .help("The input file in `.clstr` format, or `-` for stdin.")
+                        .id("FILE")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("output")
+                        .help("The file to write to, instead of stdout.")
+                        .id("output")
+                        .short('o')
+                        .long("output")
+                        .num_args(1)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("delimiter")
+                        .help("The separator between fields, a tab by default.")
+                        .id("delimiter")
+                        .short('d')
+                        .long("delimiter")
+                        .num_args(1)
+                        .value_parser(value_parser!(char))
+                        .default_value("\t")
+                        .hide_default_value(true),
+                )
+                .arg(
+                    Arg::new("no-header")
+                        .help("Leave out the header row.")
+                        .id("no-header")
+                        .long("no-header")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("no-representatives")
+                        .help("Leave out the rows of the representatives.")
+                        .id("no-representatives")
+                        .long("no-representatives")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("strand")
+                        .help("Add a column with the CD-HIT-EST strand of each sequence.")
+                        .id("strand")
+                        .long("strand")
+                        .action(ArgAction::SetTrue),
+                )
+        )
+        .subcommand(
+            Command::new("validate")
+                .about("Check a file for problems such as missing representatives, duplicate sequence IDs, identities outside 0 to 100% and truncation. Exits non-zero if any errors are found.")
+                .arg(
+                    Arg::new("FILE")
+                        .help("The input file in `.clstr` format, or `-` for stdin.")
+                        .id("FILE")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("strict")
+                        .help("Treat warnings, such as out of order indices, as errors.")
+                        .id("strict")
+                        .long("strict")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("quiet")
+                        .help("Print only whether the file passed, not each problem.")
+                        .id("quiet")
+                        .short('q')
+                        .long("quiet")
+                        .action(ArgAction::SetTrue),
+                )
+        )
+        .subcommand(
+            Command::new("rename")
+                .about("Replace sequence IDs using a tab-separated file of old and new IDs, one pair per line, and write the result to stdout.")
+                .arg(
+                    Arg::new("FILE")
+                        .help("The input file in `.clstr` format, or `-` for stdin.")
+                        .id("FILE")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("MAPPING")
+                        .help("The file of `old_id<TAB>new_id` lines.")
+                        .id("MAPPING")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .index(2),
+                )
+                .arg(
+                    Arg::new("strict")
+                        .help("Fail if any sequence ID is not in the mapping, rather than leaving it unchanged.")
+                        .id("strict")
+                        .long("strict")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("output")
+                        .help("The file to write to, instead of stdout. Gzipped if it ends in `.gz`.")
+                        .id("output")
+                        .short('o')
+                        .long("output")
+                        .num_args(1)
+                        .value_parser(value_parser!(PathBuf)),
+                )
         )
         .get_matches()
 }
 
+/// The `--mapping` option of the subcommands that renumber clusters.
+fn mapping_arg() -> Arg {
+    Arg::new("mapping")
+        .help("Also write a TSV of each written cluster's original ID and its new ID to this file.")
+        .id("mapping")
+        .long("mapping")
+        .num_args(1)
+        .value_parser(value_parser!(PathBuf))
+}
+
+/// The `--progress` flag of the subcommands that read whole files.
+fn progress_arg() -> Arg {
+    Arg::new("progress")
+        .help("Report the number of clusters parsed so far on stderr.")
+        .id("progress")
+        .long("progress")
+        .action(ArgAction::SetTrue)
+}
+
+/// Parses the clusters in the file at `path`, reporting progress on stderr
+/// if `--progress` was given.
+fn parse_with_progress(
+    matches: &ArgMatches,
+    path: &Path,
+) -> ClstrResult<Box<dyn Iterator<Item = ClstrResult<Cluster>>>> {
+    let parser = clstr::from_path(path)?;
+    if matches.get_flag("progress") {
+        Ok(Box::new(
+            parser.with_progress(|n| eprintln!("parsed {n} clusters")),
+        ))
+    } else {
+        Ok(Box::new(parser))
+    }
+}
+
+/// Writes the old and new IDs recorded by `clstr::renumber_with_mapping` to
+/// the file given with `--mapping`, if any.
+fn write_mapping(matches: &ArgMatches, mapping: &[(usize, usize)]) -> ClstrResult<()> {
+    let Some(path) = matches.get_one::<PathBuf>("mapping") else {
+        return Ok(());
+    };
+
+    let mut file = std::io::BufWriter::new(File::create(path)?);
+    writeln!(file, "old_cluster_id\tnew_cluster_id")?;
+    for (old, new) in mapping {
+        writeln!(file, "{old}\t{new}")?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// Write `clusters` and then finish the output, so that an error writing a
+/// gzip trailer is reported rather than lost when the writer is dropped.
+fn write_and_finish<'a, W, I>(mut writer: ClstrWriter<W>, clusters: I) -> ClstrResult<()>
+where
+    W: Finish,
+    I: IntoIterator<Item = &'a Cluster>,
+{
+    writer.write_clusters(clusters)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// The path that output files are named after, which is the input file
+/// itself, or `stdin` in the current directory when reading from stdin.
+fn output_base(clstr_file: &Path) -> PathBuf {
+    if clstr_file == Path::new("-") {
+        PathBuf::from("stdin")
+    } else {
+        clstr_file.to_path_buf()
+    }
+}
+
 fn filter_n(matches: &ArgMatches) -> ClstrResult<()> {
     let clstr_file = matches.get_one::<PathBuf>("FILE").unwrap().clone();
     let filter_threshold = *matches.get_one::<usize>("filter-number").unwrap();
+    let max = matches.get_one::<usize>("max").copied();
+
+    let (extension, complement_extension) = match max {
+        Some(max) if max < filter_threshold => {
+            eprintln!("clstr error: --max {max} is less than --filter-number {filter_threshold}");
+            std::process::exit(1);
+        }
+        Some(max) => (
+            format!("between_{filter_threshold}_and_{max}.clstr"),
+            format!("not_between_{filter_threshold}_and_{max}.clstr"),
+        ),
+        None => (
+            format!("more_than_{filter_threshold}.clstr"),
+            format!("fewer_than_{filter_threshold}.clstr"),
+        ),
+    };
 
     let parser = clstr::from_path(clstr_file.clone())?;
 
-    let mut out_file =
-        clstr::to_path(clstr_file.with_extension(format!("more_than_{filter_threshold}.clstr")))?;
-    for cluster in parser {
-        let cluster = cluster?;
+    let mut out_file = clstr::to_path(output_base(&clstr_file).with_extension(extension))?;
+    let mut mapping = Vec::new();
 
-        if cluster.size() >= filter_threshold {
-            out_file.write_cluster(&cluster)?;
+    if matches.get_flag("complement") {
+        let mut complement_file =
+            clstr::to_path(output_base(&clstr_file).with_extension(complement_extension))?;
+        // each output is numbered from 0 on its own, so the renumbering is
+        // done here rather than over the whole stream
+        let mut next_complement_id = 0;
+        for cluster in parser {
+            let mut cluster = cluster?;
+            let size = cluster.size();
+            if size >= filter_threshold && max.is_none_or(|max| size <= max) {
+                let new_id = mapping.len();
+                mapping.push((cluster.cluster_id(), new_id));
+                cluster.set_cluster_id(new_id);
+                out_file.write_cluster(&cluster)?;
+            } else {
+                cluster.set_cluster_id(next_complement_id);
+                next_complement_id += 1;
+                complement_file.write_cluster(&cluster)?;
+            }
+        }
+        complement_file.finish()?;
+    } else {
+        // stream rather than collect, as most clusters may pass, numbering the
+        // output from 0 so that it has no gaps
+        let filtered = parser.filter_size_range(filter_threshold, max);
+        for cluster in clstr::renumber_with_mapping(filtered, &mut mapping) {
+            out_file.write_cluster(&cluster?)?;
         }
     }
+    out_file.finish()?;
 
-    Ok(())
+    write_mapping(matches, &mapping)
 }
 
 fn top_n(matches: &ArgMatches) -> ClstrResult<()> {
     let clstr_file = matches.get_one::<PathBuf>("FILE").unwrap().clone();
     let cluster_number = *matches.get_one::<usize>("cluster-number").unwrap();
+    let by_sequences = matches.get_flag("by-sequences");
 
-    let parser = clstr::from_path(clstr_file.clone())?;
+    let (clusters, suffix) = if by_sequences {
+        // the number of clusters needed is not known up front, so read them all
+        let file = ClstrFile::from(
+            parse_with_progress(matches, &clstr_file)?.collect::<ClstrResult<Vec<_>>>()?,
+        );
+        let top: Vec<Cluster> = file
+            .top_by_cumulative_sequences(cluster_number)
+            .into_iter()
+            .cloned()
+            .collect();
+        (top, format!("top{cluster_number}_sequences.clstr"))
+    } else {
+        // keep only the largest clusters while streaming the file, largest first
+        let top = clstr::top_n_by(
+            parse_with_progress(matches, &clstr_file)?,
+            cluster_number,
+            Cluster::size,
+        )?;
+        (top, format!("top{cluster_number}.clstr"))
+    };
+
+    // and write them to file, numbered from 0
+    let mut mapping = Vec::new();
+    let top = clstr::renumber_with_mapping(clusters.into_iter().map(Ok), &mut mapping)
+        .collect::<ClstrResult<Vec<_>>>()?;
+    write_and_finish(
+        clstr::to_path(output_base(&clstr_file).with_extension(suffix))?,
+        &top,
+    )?;
+
+    write_mapping(matches, &mapping)
+}
 
-    // get all the clusters from the parser, sort them by cluster size, with
-    // largest first
-    let clusters: ClstrResult<Vec<Cluster>> = parser.into_iter().collect();
-    let mut clusters = clusters?;
-    clusters.sort_by_key(|b| std::cmp::Reverse(b.size()));
+fn cat(matches: &ArgMatches) -> ClstrResult<()> {
+    let clstr_files = matches.get_many::<PathBuf>("FILES").unwrap();
 
-    // now filter to get the top cluster_number clusters
-    let clusters = clusters.into_iter().take(cluster_number);
+    let files = clstr_files
+        .map(ClstrFile::from_path)
+        .collect::<ClstrResult<Vec<_>>>()?;
+    let merged = clstr::merge_all(files);
 
-    // and write these to file
-    let mut out_file =
-        clstr::to_path(clstr_file.with_extension(format!("top{cluster_number}.clstr")))?;
-    for cluster in clusters {
-        out_file.write_cluster(&cluster)?;
+    match matches.get_one::<PathBuf>("output") {
+        Some(output) => write_and_finish(clstr::to_path(output)?, &merged),
+        None => write_and_finish(clstr::to_writer(std::io::stdout().lock()), &merged),
     }
+}
 
-    Ok(())
+fn extract(matches: &ArgMatches) -> ClstrResult<()> {
+    let clstr_file = matches.get_one::<PathBuf>("FILE").unwrap().clone();
+    let cluster_id = *matches.get_one::<usize>("cluster-id").unwrap();
+
+    // stream the file, stopping at the first cluster with the ID
+    for cluster in clstr::from_path(&clstr_file)? {
+        let cluster = cluster?;
+        if cluster.cluster_id() == cluster_id {
+            return match matches.get_one::<PathBuf>("output") {
+                Some(output) => write_and_finish(clstr::to_path(output)?, [&cluster]),
+                None => write_and_finish(clstr::to_writer(std::io::stdout().lock()), [&cluster]),
+            };
+        }
+    }
+
+    eprintln!(
+        "clstr error: no cluster with ID {cluster_id} in {}",
+        clstr_file.display()
+    );
+    std::process::exit(1);
 }
 
 /// A function to read the FASTA file and return a map of sequence ID to sequence data.
@@ -198,7 +785,14 @@ fn write_cluster_to_fasta<P: std::io::Write>(
             writer.write_record(&record)?;
         } else {
             // FIXME: should this be a hard error?
-            eprintln!("Warning: sequence ID {} not found in FASTA", sequence.id());
+            if sequence.maybe_truncated() {
+                eprintln!(
+                    "Warning: sequence ID {} not found in FASTA, likely truncated by CD-HIT",
+                    sequence.id()
+                );
+            } else {
+                eprintln!("Warning: sequence ID {} not found in FASTA", sequence.id());
+            }
         }
     }
 
@@ -212,24 +806,24 @@ fn to_fasta(matches: &ArgMatches) -> ClstrResult<()> {
     // will this work for massive fastas..?
     let fasta_map = read_fasta(database_file)?;
 
-    let cluster_parser = clstr::from_path(clstr_file.clone())?;
-
-    for cluster in cluster_parser {
+    for cluster in parse_with_progress(matches, &clstr_file)? {
         let cluster = cluster?;
+        // a cluster that has lost its `*` is named after its longest sequence
+        let cluster_id = if let Some(representative_cluster_id) =
+            cluster.representative_or_longest().map(|e| e.id())
+        {
+            let rcid = fasta_map
+                .get(representative_cluster_id)
+                .map(|(desc, _)| desc.clone())
+                .unwrap_or_else(|| "no-description".to_string());
 
-        let cluster_id =
-            if let Some(representative_cluster_id) = cluster.get_representative().map(|e| e.id()) {
-                let rcid = fasta_map
-                    .get(representative_cluster_id)
-                    .map(|(desc, _)| desc.clone())
-                    .unwrap_or_else(|| "no-description".to_string());
-
-                rcid.replace(" ", "_").replace("/", "_")
-            } else {
-                "No representative".to_string()
-            };
+            rcid.replace(" ", "_").replace("/", "_")
+        } else {
+            format!("empty_cluster_{}", cluster.cluster_id())
+        };
 
-        let out_file = File::create(clstr_file.with_extension(format!("{cluster_id}.fasta")))?;
+        let out_file =
+            File::create(output_base(&clstr_file).with_extension(format!("{cluster_id}.fasta")))?;
         write_cluster_to_fasta(&cluster, &fasta_map, out_file)?;
     }
 
@@ -239,44 +833,490 @@ fn to_fasta(matches: &ArgMatches) -> ClstrResult<()> {
 fn stats(matches: &ArgMatches) -> ClstrResult<()> {
     let clstr_file = matches.get_one::<PathBuf>("FILE").unwrap().clone();
     let table = matches.get_flag("table");
-    let parser = clstr::from_path(clstr_file.clone())?;
+    let identities = matches.get_flag("identities");
+    let validate = matches.get_flag("validate");
+    let percentiles = matches.get_flag("percentiles");
+    let histogram = matches.get_flag("histogram");
+    let singletons = matches.get_flag("singletons");
+    let lengths = matches.get_flag("lengths");
+    let json = matches.get_flag("json");
+    // a truncated file is still summarised, but with a warning and a
+    // non-zero exit, as its totals may be short
+    let options = ParseOptions {
+        on_truncation: TruncationPolicy::WarnAndKeep,
+        ..Default::default()
+    };
+    let mut parser = ClstrParser::with_options(ClstrReader::from_path(&clstr_file)?, options);
 
     // make a writer to stdout
     let stdout = std::io::stdout();
     let mut handle = stdout.lock();
 
+    if validate {
+        let warnings = parser.validate()?;
+        for warning in &warnings {
+            let _ = writeln!(handle, "{warning}");
+        }
+        let _ = writeln!(handle, "{} issue(s) found", warnings.len());
+        return Ok(());
+    }
+
     if table {
-        for cluster in parser {
+        for cluster in &mut parser {
             let cluster = cluster?;
             let _ = writeln!(handle, "{}\t{}", cluster.cluster_id(), cluster.size());
         }
-        return Ok(());
+        return check_truncation(&parser, &clstr_file);
     }
 
-    let mut cluster_count = 0;
-    let mut sequence_count = 0;
+    if percentiles || histogram {
+        // only the number of clusters of each size is kept
+        let distribution = clstr::size_distribution(&mut parser)?;
 
-    for cluster in parser {
-        let cluster = cluster?;
-        cluster_count += 1;
-        sequence_count += cluster.size();
+        if percentiles {
+            let _ = writeln!(handle, "percentile\tcluster_size");
+            for p in [5, 10, 25, 50, 75, 90, 95, 99] {
+                // nearest rank
+                let rank = (p * distribution.cluster_count()).div_ceil(100).max(1);
+                let size = distribution
+                    .nth_smallest(rank)
+                    .map_or("NA".to_string(), |s| s.to_string());
+                let _ = writeln!(handle, "{p}\t{size}");
+            }
+        }
+
+        if histogram {
+            // bucket i holds sizes from 2^i up to 2^(i + 1) - 1
+            let mut buckets: Vec<usize> = Vec::new();
+            for (&size, &count) in distribution.counts() {
+                let bucket = size.max(1).ilog2() as usize;
+                if buckets.len() <= bucket {
+                    buckets.resize(bucket + 1, 0);
+                }
+                buckets[bucket] += count as usize;
+            }
+
+            let widest = buckets.iter().copied().max().unwrap_or(0).max(1);
+            for (i, count) in buckets.iter().enumerate() {
+                let bar = "#".repeat((count * 50).div_ceil(widest));
+                let (low, high) = (1usize << i, (1usize << (i + 1)) - 1);
+                let _ = writeln!(handle, "{low}-{high}\t{count}\t{bar}");
+            }
+        }
+        return check_truncation(&parser, &clstr_file);
     }
 
-    let avg_sequence_count_per_cluster = sequence_count as f64 / cluster_count as f64;
+    if identities {
+        // identities are missing for clusters with only a representative
+        let fmt_identity = |i: Option<f32>| i.map_or("NA".to_string(), |i| format!("{i:.2}"));
+
+        let _ = writeln!(
+            handle,
+            "Cluster\tSize\tMin identity\tMean identity\tMax identity"
+        );
+        for cluster in &mut parser {
+            let cluster = cluster?;
+            let _ = writeln!(
+                handle,
+                "{}\t{}\t{}\t{}\t{}",
+                cluster.cluster_id(),
+                cluster.size(),
+                fmt_identity(cluster.min_identity()),
+                fmt_identity(cluster.average_identity()),
+                fmt_identity(cluster.max_identity())
+            );
+        }
+        return check_truncation(&parser, &clstr_file);
+    }
+
+    let summary = clstr::summarize(&mut parser)?;
+    if json {
+        write_summary_json(&mut handle, &summary);
+    } else {
+        write_summary_tsv(&mut handle, &summary, singletons, lengths);
+    }
+
+    check_truncation(&parser, &clstr_file)
+}
+
+/// Warns and exits non-zero if `parser` found its input truncated, after
+/// `stats` has printed what it could read.
+fn check_truncation<R: std::io::BufRead>(parser: &ClstrParser<R>, path: &Path) -> ClstrResult<()> {
+    if parser.is_truncated() {
+        eprintln!(
+            "Warning: {} is truncated, the last line has no newline, so the figures above may be incomplete",
+            path.display()
+        );
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Writes the default `stats` output, a tiny TSV of `summary`.
+fn write_summary_tsv<W: Write>(
+    handle: &mut W,
+    summary: &ClstrSummary,
+    singletons: bool,
+    lengths: bool,
+) {
+    let ClstrSummary {
+        cluster_count,
+        sequence_count,
+        singleton_count,
+        total_length,
+        ..
+    } = *summary;
+    // an empty file gives NaN, as it always has
+    let avg_sequence_count_per_cluster = summary.mean_cluster_size.unwrap_or(f64::NAN);
+
+    let mut header = "Cluster count\tSequence count\tAvg seqs per cluster".to_string();
+    let mut row = format!("{cluster_count}\t{sequence_count}\t{avg_sequence_count_per_cluster}");
+    if singletons {
+        let singleton_fraction = singleton_count as f64 / cluster_count as f64;
+        // each singleton holds exactly one sequence
+        let sequence_fraction = singleton_count as f64 / sequence_count as f64;
+        header.push_str("\tSingleton count\tSingleton fraction\tSequence fraction in singletons");
+        row.push_str(&format!(
+            "\t{singleton_count}\t{singleton_fraction}\t{sequence_fraction}"
+        ));
+    }
+    if lengths {
+        let avg_total_length_per_cluster = total_length as f64 / cluster_count as f64;
+        header.push_str("\tAvg total length per cluster");
+        row.push_str(&format!("\t{avg_total_length_per_cluster}"));
+    }
+    let _ = writeln!(handle, "{header}");
+    let _ = writeln!(handle, "{row}");
+}
+
+/// Writes `summary` as a single JSON object, with `null` for the values an
+/// empty file does not have.
+fn write_summary_json<W: Write>(handle: &mut W, summary: &ClstrSummary) {
+    fn or_null<T: ToString>(value: Option<T>) -> String {
+        value.map_or("null".to_string(), |v| v.to_string())
+    }
 
-    // write a tiny tsv
     let _ = writeln!(
         handle,
-        "Cluster count\tSequence count\tAvg seqs per cluster"
+        "{{\"cluster_count\": {}, \"sequence_count\": {}, \"avg_seqs_per_cluster\": {}, \"singleton_count\": {}, \"max_cluster_size\": {}, \"min_cluster_size\": {}, \"largest_cluster_id\": {}, \"median_cluster_size\": {}, \"n50\": {}, \"total_length\": {}}}",
+        summary.cluster_count,
+        summary.sequence_count,
+        or_null(summary.mean_cluster_size),
+        summary.singleton_count,
+        or_null(summary.max_cluster_size),
+        or_null(summary.min_cluster_size),
+        or_null(summary.largest_cluster_id),
+        or_null(summary.median_cluster_size),
+        or_null(summary.n50),
+        summary.total_length
     );
+}
+
+fn split(matches: &ArgMatches) -> ClstrResult<()> {
+    let clstr_file = matches.get_one::<PathBuf>("FILE").unwrap().clone();
+    let count = *matches.get_one::<u64>("count").unwrap() as usize;
+    let output_dir = matches.get_one::<PathBuf>("output-dir").unwrap();
+    let by_sequences = matches.get_flag("by-sequences");
+
+    let clusters = ClstrFile::from_path(&clstr_file)?;
+
+    // where the middle of each cluster falls in the input decides its part,
+    // which keeps the parts in file order
+    let total = if by_sequences {
+        clusters.iter().map(Cluster::size).sum()
+    } else {
+        clusters.len()
+    };
+
+    std::fs::create_dir_all(output_dir)?;
+    let mut parts = (0..count)
+        .map(|i| clstr::to_path(output_dir.join(format!("part_{i:03}.clstr"))))
+        .collect::<ClstrResult<Vec<_>>>()?;
+
+    let mut before = 0;
+    for cluster in &clusters {
+        let size = if by_sequences { cluster.size() } else { 1 };
+        let part = ((2 * before + size) * count)
+            .checked_div(2 * total)
+            .unwrap_or(0);
+        parts[part.min(count - 1)].write_cluster(cluster)?;
+        before += size;
+    }
+
+    for part in parts {
+        part.finish()?;
+    }
+
+    Ok(())
+}
+
+fn sample(matches: &ArgMatches) -> ClstrResult<()> {
+    let clstr_file = matches.get_one::<PathBuf>("FILE").unwrap().clone();
+    let count = *matches.get_one::<usize>("count").unwrap();
+    let keep_ids = matches.get_flag("keep-ids");
+    let mut rng = match matches.get_one::<u64>("seed") {
+        Some(&seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    // reservoir sampling, keeping each cluster's position to restore file
+    // order afterwards
+    let mut reservoir: Vec<(usize, Cluster)> = Vec::with_capacity(count);
+    for (position, cluster) in clstr::from_path(&clstr_file)?.enumerate() {
+        let cluster = cluster?;
+        if position < count {
+            reservoir.push((position, cluster));
+        } else {
+            let slot = rng.gen_range(0..=position);
+            if slot < count {
+                reservoir[slot] = (position, cluster);
+            }
+        }
+    }
+    reservoir.sort_unstable_by_key(|(position, _)| *position);
+
+    let mut sampled: Vec<Cluster> = reservoir.into_iter().map(|(_, c)| c).collect();
+    if !keep_ids {
+        sampled = clstr::renumber_clusters(sampled).collect();
+    }
+
+    match matches.get_one::<PathBuf>("output") {
+        Some(output) => write_and_finish(clstr::to_path(output)?, &sampled),
+        None => write_and_finish(clstr::to_writer(std::io::stdout().lock()), &sampled),
+    }
+}
+
+fn filter_id(matches: &ArgMatches) -> ClstrResult<()> {
+    let clstr_file = matches.get_one::<PathBuf>("FILE").unwrap().clone();
+    let require_representative = matches.get_flag("require-representative");
+
+    let ids_file = match matches.get_one::<PathBuf>("ids-file") {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => String::new(),
+    };
+    let ids: HashSet<&str> = matches
+        .get_many::<String>("id")
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .chain(ids_file.lines().map(str::trim).filter(|id| !id.is_empty()))
+        .collect();
+
+    let clusters = ClstrFile::from_path(&clstr_file)?;
+    let matched = if require_representative {
+        clusters
+            .iter()
+            .filter(|c| {
+                c.get_representative()
+                    .is_some_and(|rep| ids.contains(rep.id()))
+            })
+            .collect()
+    } else {
+        clusters.filter_by_ids(&ids)
+    };
+
+    write_and_finish(clstr::to_writer(std::io::stdout().lock()), matched)
+}
+
+/// Maps each sequence ID in the file at `path` to the ID of the first cluster
+/// it appears in, as [`clstr::ClusterSet::cluster_of`] does, returning the IDs
+/// in file order alongside.
+fn sequence_clusters(path: &Path) -> ClstrResult<(Vec<String>, HashMap<String, usize>)> {
+    let mut order = Vec::new();
+    let mut clusters = HashMap::new();
+
+    for record in clstr::from_path(path)?.into_sequences() {
+        let (cluster_id, sequence) = record?;
+        if let Entry::Vacant(entry) = clusters.entry(sequence.id().to_string()) {
+            order.push(entry.key().clone());
+            entry.insert(cluster_id);
+        }
+    }
+
+    Ok((order, clusters))
+}
+
+fn compare(matches: &ArgMatches) -> ClstrResult<()> {
+    let file1 = matches.get_one::<PathBuf>("FILE1").unwrap();
+    let file2 = matches.get_one::<PathBuf>("FILE2").unwrap();
+    let only_changed = matches.get_flag("only-changed");
+
+    let (order1, clusters1) = sequence_clusters(file1)?;
+    let (order2, clusters2) = sequence_clusters(file2)?;
+
+    let stdout = std::io::stdout();
+    let mut handle = stdout.lock();
+
+    let fmt_cluster = |c: Option<&usize>| c.map_or("NA".to_string(), |c| c.to_string());
+
+    let (mut same, mut moved, mut only_one) = (0, 0, 0);
     let _ = writeln!(
         handle,
-        "{cluster_count}\t{sequence_count}\t{avg_sequence_count_per_cluster}"
+        "sequence_id\tcluster_in_file1\tcluster_in_file2\tchanged"
     );
+    // sequences of the first file in its order, then those only in the second
+    let ids = order1
+        .iter()
+        .chain(order2.iter().filter(|id| !clusters1.contains_key(*id)));
+    for id in ids {
+        let (c1, c2) = (clusters1.get(id), clusters2.get(id));
+        match (c1, c2) {
+            (Some(a), Some(b)) if a == b => same += 1,
+            (Some(_), Some(_)) => moved += 1,
+            _ => only_one += 1,
+        }
+
+        let changed = c1 != c2;
+        if changed || !only_changed {
+            let _ = writeln!(
+                handle,
+                "{id}\t{}\t{}\t{changed}",
+                fmt_cluster(c1),
+                fmt_cluster(c2)
+            );
+        }
+    }
+
+    // the summary goes to stderr to keep stdout a plain TSV
+    eprintln!("{same} sequence(s) in the same cluster, {moved} moved, {only_one} in only one file");
 
     Ok(())
 }
 
+fn export(matches: &ArgMatches) -> ClstrResult<()> {
+    let clstr_file = matches.get_one::<PathBuf>("FILE").unwrap().clone();
+    let options = clstr::MembershipOptions {
+        header: !matches.get_flag("no-header"),
+        delimiter: *matches.get_one::<char>("delimiter").unwrap(),
+        include_representatives: !matches.get_flag("no-representatives"),
+        strand: matches.get_flag("strand"),
+    };
+    // strands are only parsed from CD-HIT-EST output
+    let parser = ClstrParser::with_options(
+        ClstrReader::from_path(&clstr_file)?,
+        ParseOptions {
+            cd_hit_est: options.strand,
+            ..ParseOptions::default()
+        },
+    );
+
+    match matches.get_one::<PathBuf>("output") {
+        Some(output) => clstr::write_membership_table(
+            parser,
+            std::io::BufWriter::new(File::create(output)?),
+            &options,
+        ),
+        None => clstr::write_membership_table(parser, std::io::stdout().lock(), &options),
+    }
+}
+
+fn validate(matches: &ArgMatches) -> ClstrResult<()> {
+    let clstr_file = matches.get_one::<PathBuf>("FILE").unwrap().clone();
+    let strict = matches.get_flag("strict");
+    let quiet = matches.get_flag("quiet");
+
+    // malformed lines fail parsing outright, while identities outside 0 to
+    // 100% and a missing final newline are left for the checks to report
+    let options = ParseOptions {
+        strict: true,
+        max_identity_value: f32::INFINITY,
+        min_identity_value: f32::NEG_INFINITY,
+        on_truncation: TruncationPolicy::WarnAndKeep,
+        ..Default::default()
+    };
+    let parser = ClstrParser::with_options(ClstrReader::from_path(&clstr_file)?, options);
+    let problems = parser.validate()?;
+
+    let mut errors = 0;
+    let stdout = std::io::stdout();
+    let mut handle = stdout.lock();
+    for problem in &problems {
+        let is_error = strict || problem.is_error();
+        if is_error {
+            errors += 1;
+        }
+        if !quiet {
+            let level = if is_error { "error" } else { "warning" };
+            let _ = writeln!(handle, "{level}: {problem}");
+        }
+    }
+
+    let warnings = problems.len() - errors;
+    if quiet {
+        let _ = writeln!(handle, "{}", if errors == 0 { "PASS" } else { "FAIL" });
+    } else {
+        let _ = writeln!(handle, "{errors} error(s), {warnings} warning(s)");
+    }
+
+    if errors > 0 {
+        let _ = handle.flush();
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Read a mapping of old to new sequence IDs, one tab-separated pair per
+/// line, exiting on a malformed line or an old ID given twice.
+fn read_id_mapping(path: &Path) -> ClstrResult<HashMap<String, String>> {
+    let mut mapping = HashMap::new();
+    for (i, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((old_id, new_id)) = line.split_once('\t') else {
+            eprintln!(
+                "clstr error: line {} of {} is not `old_id<TAB>new_id`",
+                i + 1,
+                path.display()
+            );
+            std::process::exit(1);
+        };
+        if mapping
+            .insert(old_id.to_string(), new_id.trim_end().to_string())
+            .is_some()
+        {
+            eprintln!(
+                "clstr error: sequence ID {old_id} is mapped more than once in {}",
+                path.display()
+            );
+            std::process::exit(1);
+        }
+    }
+    Ok(mapping)
+}
+
+fn rename(matches: &ArgMatches) -> ClstrResult<()> {
+    let clstr_file = matches.get_one::<PathBuf>("FILE").unwrap().clone();
+    let mapping = read_id_mapping(matches.get_one::<PathBuf>("MAPPING").unwrap())?;
+    let strict = matches.get_flag("strict");
+
+    let file = ClstrFile::from_path(&clstr_file)?;
+    let mut unmapped = file
+        .iter()
+        .flat_map(|c| c.iter())
+        .filter(|s| !mapping.contains_key(s.id()));
+    if strict {
+        if let Some(seq) = unmapped.next() {
+            eprintln!(
+                "clstr error: sequence ID {} is not in the mapping",
+                seq.id()
+            );
+            std::process::exit(1);
+        }
+    } else {
+        let count = unmapped.count();
+        if count > 0 {
+            eprintln!("Warning: {count} sequence ID(s) not in the mapping were left unchanged");
+        }
+    }
+
+    let renamed = file.rename_sequences(&mapping);
+    match matches.get_one::<PathBuf>("output") {
+        Some(output) => write_and_finish(clstr::to_path(output)?, renamed.iter()),
+        None => write_and_finish(clstr::to_writer(std::io::stdout().lock()), renamed.iter()),
+    }
+}
+
 fn main() -> ClstrResult<()> {
     let matches = parse_args();
 
@@ -285,11 +1325,21 @@ fn main() -> ClstrResult<()> {
         Some(("tofasta", matches)) => to_fasta(matches),
         Some(("filtern", matches)) => filter_n(matches),
         Some(("stats", matches)) => stats(matches),
+        Some(("cat", matches)) => cat(matches),
+        Some(("extract", matches)) => extract(matches),
+        Some(("compare", matches)) => compare(matches),
+        Some(("split", matches)) => split(matches),
+        Some(("sample", matches)) => sample(matches),
+        Some(("filterid", matches)) => filter_id(matches),
+        Some(("export", matches)) => export(matches),
+        Some(("validate", matches)) => validate(matches),
+        Some(("rename", matches)) => rename(matches),
         _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
     };
 
-    if result.is_err() {
-        eprintln!("clstr error: {}", result.unwrap_err());
+    if let Err(err) = result {
+        eprintln!("clstr error: {err}");
+        std::process::exit(1);
     }
 
     Ok(())
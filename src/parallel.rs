@@ -0,0 +1,379 @@
+/*!
+Parallel parsing of `.clstr` files with rayon, enabled by the `parallel` feature.
+*/
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::{
+    BadHeaderPolicy, ClstrParser, ClstrReader, Cluster, DuplicateIdPolicy, Error, ErrorKind,
+    ParseOptions, Result, WarningCallback,
+};
+
+/// Chunks smaller than this are not worth handing to a thread of their own.
+const MIN_CHUNK_LEN: u64 = 1 << 20;
+
+/// A part of the input that can be parsed independently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Chunk {
+    /// The bytes `start..end` of an uncompressed file, beginning at a
+    /// `>Cluster` header.
+    Range(u64, u64),
+    /// The whole file, for input that cannot be split.
+    Whole,
+}
+
+/// Function to parse a `.clstr` file from a path across the rayon thread pool.
+///
+/// Uncompressed files are split into chunks at `>Cluster` headers and the
+/// chunks are parsed in parallel; collecting the iterator still yields the
+/// clusters in file order. Cluster IDs are always taken from the headers.
+/// Line numbers and byte offsets in errors count from the start of the file,
+/// for which the lines of each chunk are counted first, also in parallel.
+/// Compressed files cannot be split, so they are parsed by a single thread.
+pub fn par_from_path<P: AsRef<Path>>(
+    path: P,
+) -> Result<impl ParallelIterator<Item = Result<Cluster>>> {
+    par_from_path_with_options(path, ParseOptions::default())
+}
+
+/// Function to parse a `.clstr` file from a path across the rayon thread pool
+/// with the given options, as [`par_from_path`] does.
+///
+/// Each chunk is parsed with its own copy of `options`, sharing
+/// [`ParseOptions::on_warning`] across threads. Options that number clusters
+/// across the whole file cannot hold when chunks are parsed apart, so
+/// `preserve_cluster_ids: false`, any [`ParseOptions::on_duplicate_id`] but
+/// [`DuplicateIdPolicy::Keep`] and [`BadHeaderPolicy::AssignSequential`] are
+/// an error.
+pub fn par_from_path_with_options<P: AsRef<Path>>(
+    path: P,
+    options: ParseOptions,
+) -> Result<impl ParallelIterator<Item = Result<Cluster>>> {
+    if !options.preserve_cluster_ids
+        || options.on_duplicate_id != DuplicateIdPolicy::Keep
+        || options.on_bad_header == BadHeaderPolicy::AssignSequential
+    {
+        return Err(Error::new(ErrorKind::ReadRecord(
+            "cluster numbering across the whole file cannot be done in parallel".to_string(),
+        )));
+    }
+
+    let path = path.as_ref().to_path_buf();
+    let (options, on_warning) = split_options(options);
+
+    let chunks = match ClstrReader::from_path(&path)? {
+        ClstrReader::Plain(mut reader) => {
+            let len = reader.get_ref().metadata()?.len();
+            let n_chunks = (len / MIN_CHUNK_LEN).clamp(1, rayon::current_num_threads() as u64 * 4);
+            split_chunks(&mut reader, len, n_chunks)?
+        }
+        _ => vec![Chunk::Whole],
+    };
+    let first_lines = first_lines(&path, &chunks)?;
+
+    Ok(chunks
+        .into_par_iter()
+        .zip(first_lines)
+        .flat_map_iter(move |(chunk, lines_before)| {
+            parse_chunk(
+                &path,
+                chunk,
+                lines_before,
+                chunk_options(&options, &on_warning),
+            )
+        }))
+}
+
+/// Split `options` into a copy of everything but the warning callback, which
+/// cannot be cloned, and the callback, which can then be shared.
+fn split_options(mut options: ParseOptions) -> (ParseOptions, Option<Arc<WarningCallback>>) {
+    let on_warning = options.on_warning.take().map(Arc::new);
+    (options, on_warning)
+}
+
+/// Returns a copy of `options` for one chunk, calling the shared
+/// `on_warning`.
+fn chunk_options(
+    options: &ParseOptions,
+    on_warning: &Option<Arc<WarningCallback>>,
+) -> ParseOptions {
+    ParseOptions {
+        sequence_type: options.sequence_type,
+        strict: options.strict,
+        preserve_cluster_ids: options.preserve_cluster_ids,
+        max_identity_value: options.max_identity_value,
+        min_identity_value: options.min_identity_value,
+        on_warning: on_warning
+            .clone()
+            .map(|f| -> WarningCallback { Box::new(move |line, msg| f(line, msg)) }),
+        on_duplicate_id: options.on_duplicate_id,
+        cd_hit_est: options.cd_hit_est,
+        on_invalid_utf8: options.on_invalid_utf8,
+        on_truncation: options.on_truncation,
+        description_length: options.description_length,
+        on_bad_header: options.on_bad_header,
+        check_indices: options.check_indices,
+    }
+}
+
+/// Split a file of `len` bytes into at most `n_chunks` chunks, each starting
+/// at a header line.
+fn split_chunks<R: BufRead + Seek>(reader: &mut R, len: u64, n_chunks: u64) -> Result<Vec<Chunk>> {
+    let mut starts = vec![0];
+    let mut line = Vec::new();
+
+    for i in 1..n_chunks {
+        let target = len * i / n_chunks;
+        let last = *starts.last().unwrap_or(&0);
+        if target <= last {
+            continue;
+        }
+
+        // skip to the first line starting at or after `target`
+        reader.seek(SeekFrom::Start(target - 1))?;
+        let mut pos = target - 1 + reader.read_until(b'\n', &mut line)? as u64;
+
+        // then on to the next header
+        loop {
+            line.clear();
+            let n = reader.read_until(b'\n', &mut line)?;
+            if n == 0 || line.starts_with(b">") {
+                break;
+            }
+            pos += n as u64;
+        }
+
+        if pos > last && pos < len {
+            starts.push(pos);
+        }
+    }
+
+    starts.push(len);
+    Ok(starts
+        .windows(2)
+        .map(|w| Chunk::Range(w[0], w[1]))
+        .collect())
+}
+
+/// Returns the number of lines before each of `chunks` of the file at
+/// `path`, counting the lines of each chunk in parallel.
+fn first_lines(path: &Path, chunks: &[Chunk]) -> Result<Vec<u64>> {
+    let counts = chunks
+        .par_iter()
+        .map(|&chunk| match chunk {
+            Chunk::Range(start, end) => count_lines(path, start, end),
+            Chunk::Whole => Ok(0),
+        })
+        .collect::<Result<Vec<u64>>>()?;
+
+    Ok(counts
+        .iter()
+        .scan(0, |lines_before, count| {
+            let first = *lines_before;
+            *lines_before += count;
+            Some(first)
+        })
+        .collect())
+}
+
+/// Counts the line breaks in the bytes `start..end` of the file at `path`.
+fn count_lines(path: &Path, start: u64, end: u64) -> Result<u64> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut reader = BufReader::with_capacity(1 << 16, file.take(end - start));
+
+    let mut count = 0;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(count);
+        }
+        count += buf.iter().filter(|&&b| b == b'\n').count() as u64;
+        let n = buf.len();
+        reader.consume(n);
+    }
+}
+
+/// Parse a single chunk of the file at `path` with `options`, numbering
+/// its lines on from the `lines_before` it.
+fn parse_chunk(
+    path: &Path,
+    chunk: Chunk,
+    lines_before: u64,
+    options: ParseOptions,
+) -> Box<dyn Iterator<Item = Result<Cluster>>> {
+    let opened = match chunk {
+        Chunk::Range(start, end) => File::open(path)
+            .and_then(|mut file| {
+                file.seek(SeekFrom::Start(start))?;
+                Ok(file)
+            })
+            .map(|file| -> Box<dyn Iterator<Item = Result<Cluster>>> {
+                Box::new(
+                    ClstrParser::with_options(BufReader::new(file.take(end - start)), options)
+                        .starting_at(lines_before, start),
+                )
+            })
+            .map_err(Into::into),
+        Chunk::Whole => ClstrReader::from_path(path).map(
+            |reader| -> Box<dyn Iterator<Item = Result<Cluster>>> {
+                Box::new(ClstrParser::with_options(reader, options))
+            },
+        ),
+    };
+
+    match opened {
+        Ok(parser) => parser,
+        Err(e) => Box::new(std::iter::once(Err(e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_split_chunks_on_headers() {
+        let data =
+            std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_nt.clstr")).unwrap();
+        let len = data.len() as u64;
+
+        for n_chunks in 1..20 {
+            let chunks = split_chunks(&mut Cursor::new(&data), len, n_chunks).unwrap();
+            assert!(chunks.len() as u64 <= n_chunks);
+
+            let mut expected_start = 0;
+            for chunk in chunks {
+                let Chunk::Range(start, end) = chunk else {
+                    panic!("plain input split into {chunk:?}");
+                };
+                assert_eq!(start, expected_start);
+                assert_eq!(data[start as usize], b'>');
+                expected_start = end;
+            }
+            assert_eq!(expected_start, len);
+        }
+    }
+
+    #[test]
+    fn test_par_from_path_matches_sequential() {
+        let mut data = String::new();
+        for c in 0..40_000 {
+            data.push_str(&format!(">Cluster {c}\n"));
+            for i in 0..(c % 5) {
+                data.push_str(&format!("{i}\t{}aa, >seq_{c}_{i}... at 90.00%\n", 100 + i));
+            }
+            data.push_str(&format!("{}\t500aa, >seq_{c}_rep... *\n", c % 5));
+        }
+        assert!(data.len() as u64 > 2 * MIN_CHUNK_LEN);
+
+        let path = crate::tests::temp_path("parallel.clstr");
+        std::fs::write(&path, &data).unwrap();
+
+        let sequential: Vec<Cluster> = crate::from_path(&path)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        let parallel: Vec<Cluster> = par_from_path(&path)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(parallel.len(), sequential.len());
+        for (a, b) in sequential.iter().zip(parallel.iter()) {
+            assert_eq!(a.cluster_id(), b.cluster_id());
+            assert_eq!(a.size(), b.size());
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_par_from_path_error_lines() {
+        let mut data = String::new();
+        for c in 0..80_000 {
+            data.push_str(&format!(">Cluster {c}\n0\t500aa, >seq_{c}_rep... *\n"));
+        }
+        // a bad line well into the last chunk
+        data.push_str(">Cluster 80000\n0\t50x0aa, >bad... *\n");
+        assert!(data.len() as u64 > 2 * MIN_CHUNK_LEN);
+        let path = crate::tests::temp_path("parallel_error_lines.clstr");
+        std::fs::write(&path, &data).unwrap();
+
+        let sequential = crate::from_path(&path)
+            .unwrap()
+            .collect::<Result<Vec<Cluster>>>()
+            .unwrap_err();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let parallel = pool
+            .install(|| {
+                par_from_path(&path)
+                    .unwrap()
+                    .collect::<Result<Vec<Cluster>>>()
+            })
+            .unwrap_err();
+        assert!(matches!(
+            parallel.kind(),
+            ErrorKind::Parse { line: 160_002, .. }
+        ));
+        assert_eq!(parallel.to_string(), sequential.to_string());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_par_from_path_with_options() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let nt = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_nt.clstr");
+        let options = ParseOptions {
+            cd_hit_est: true,
+            ..ParseOptions::default()
+        };
+        let clusters: Vec<Cluster> = par_from_path_with_options(nt, options)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert!(clusters[3].sequences()[0].strand().is_some());
+
+        // the callback is shared by the chunks
+        let warnings = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&warnings);
+        let options = ParseOptions {
+            on_invalid_utf8: crate::InvalidUtf8Policy::Skip,
+            on_warning: Some(Box::new(move |_, _| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            })),
+            ..ParseOptions::default()
+        };
+        let latin1 = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_latin1.clstr");
+        let clusters: Vec<Cluster> = par_from_path_with_options(latin1, options)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(warnings.load(Ordering::Relaxed), 1);
+
+        let renumbered = ParseOptions {
+            preserve_cluster_ids: false,
+            ..ParseOptions::default()
+        };
+        assert!(par_from_path_with_options(nt, renumbered).is_err());
+    }
+
+    #[test]
+    fn test_par_from_path_gzip() {
+        let gz = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test.clstr.gz");
+        let clusters: Vec<Cluster> = par_from_path(gz).unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(clusters.len(), 2);
+    }
+}
@@ -0,0 +1,149 @@
+/*!
+Clusters held in memory with hash lookups by cluster ID and by sequence ID.
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::{ClstrFile, Cluster, Result};
+
+/// A set of clusters indexed by cluster ID and by the IDs of their
+/// sequences, both built once when the set is made.
+#[derive(Debug, Default)]
+pub struct ClusterSet {
+    /// The clusters, in input order.
+    clusters: Vec<Cluster>,
+    /// Maps each cluster ID to the position of its first cluster.
+    by_cluster_id: HashMap<usize, usize>,
+    /// Maps each sequence ID to the position of the first cluster holding it.
+    by_sequence_id: HashMap<String, usize>,
+    /// The sequence IDs found in more than one cluster, in input order.
+    duplicates: Vec<String>,
+}
+
+impl ClusterSet {
+    /// Reads every cluster from the file at `path`, as [`crate::from_path`]
+    /// would.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<ClusterSet> {
+        Ok(ClstrFile::from_path(path)?.into_iter().collect())
+    }
+
+    /// Returns the cluster with the given ID. If the ID occurs more than
+    /// once, the first such cluster is returned.
+    pub fn get(&self, cluster_id: usize) -> Option<&Cluster> {
+        self.by_cluster_id
+            .get(&cluster_id)
+            .map(|&position| &self.clusters[position])
+    }
+
+    /// Returns the ID of the cluster holding the sequence with the given ID.
+    /// If the sequence is in more than one cluster, the first is returned.
+    pub fn cluster_of(&self, sequence_id: &str) -> Option<usize> {
+        self.by_sequence_id
+            .get(sequence_id)
+            .map(|&position| self.clusters[position].cluster_id())
+    }
+
+    /// Returns the sequence IDs found in more than one cluster, each once, in
+    /// the order their second cluster appears.
+    pub fn duplicates(&self) -> &[String] {
+        &self.duplicates
+    }
+
+    /// Returns the number of clusters.
+    pub fn len(&self) -> usize {
+        self.clusters.len()
+    }
+
+    /// Returns whether there are no clusters.
+    pub fn is_empty(&self) -> bool {
+        self.clusters.is_empty()
+    }
+
+    /// Returns an iterator over the clusters, in input order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Cluster> {
+        self.clusters.iter()
+    }
+}
+
+impl FromIterator<Cluster> for ClusterSet {
+    fn from_iter<I: IntoIterator<Item = Cluster>>(iter: I) -> Self {
+        let clusters: Vec<Cluster> = iter.into_iter().collect();
+        let sequence_count = clusters.iter().map(Cluster::size).sum();
+
+        let mut by_cluster_id = HashMap::with_capacity(clusters.len());
+        let mut by_sequence_id: HashMap<String, usize> = HashMap::with_capacity(sequence_count);
+        let mut duplicates = Vec::new();
+        let mut seen_duplicates = HashSet::new();
+        for (position, cluster) in clusters.iter().enumerate() {
+            by_cluster_id
+                .entry(cluster.cluster_id())
+                .or_insert(position);
+
+            for seq in cluster {
+                match by_sequence_id.get(seq.id()) {
+                    None => {
+                        by_sequence_id.insert(seq.id().to_string(), position);
+                    }
+                    // record each ID once, on reaching its second cluster
+                    Some(&first) if first != position => {
+                        if seen_duplicates.insert(seq.id()) {
+                            duplicates.push(seq.id().to_string());
+                        }
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        ClusterSet {
+            clusters,
+            by_cluster_id,
+            by_sequence_id,
+            duplicates,
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a ClusterSet {
+    type Item = &'a Cluster;
+    type IntoIter = std::slice::Iter<'a, Cluster>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.clusters.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cluster_set() {
+        let set = ClusterSet::from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_nt.clstr"))
+            .unwrap();
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.get(479).unwrap().size(), 6);
+        assert!(set.get(12).is_none());
+        assert_eq!(set.cluster_of("::SUPER_2:8753913-8754035"), Some(479));
+        assert_eq!(set.cluster_of("::SUPER_9:1-2"), None);
+        assert!(set.duplicates().is_empty());
+
+        let set: ClusterSet = crate::from_str(
+            ">Cluster 0
+0    4481aa, >a... at 99.89%
+1    7182aa, >b... *
+>Cluster 1
+0    7126aa, >a... *
+>Cluster 2
+0    7119aa, >a... *
+1    7119aa, >c... at 98.00%
+",
+        )
+        .collect::<Result<_>>()
+        .unwrap();
+        assert_eq!(set.cluster_of("a"), Some(0));
+        assert_eq!(set.duplicates(), ["a"]);
+        assert_eq!(set.iter().map(Cluster::size).sum::<usize>(), 5);
+    }
+}
@@ -0,0 +1,362 @@
+/*!
+Totals and distributions over a whole `.clstr` file, each gathered in a single
+streaming pass.
+*/
+
+use std::collections::BTreeMap;
+
+use crate::{Cluster, Result};
+
+/// Cluster and sequence counts over a whole file, returned by [`summarize`].
+/// The optional fields are `None` for a file with no clusters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClstrSummary {
+    /// The number of clusters.
+    pub cluster_count: usize,
+    /// The number of sequences over all clusters.
+    pub sequence_count: usize,
+    /// The number of clusters with exactly one sequence.
+    pub singleton_count: usize,
+    /// The summed length of every sequence, in residues.
+    pub total_length: u64,
+    /// The size of the smallest cluster.
+    pub min_cluster_size: Option<usize>,
+    /// The size of the largest cluster.
+    pub max_cluster_size: Option<usize>,
+    /// The ID of the first cluster of the largest size.
+    pub largest_cluster_id: Option<usize>,
+    /// The mean number of sequences per cluster.
+    pub mean_cluster_size: Option<f64>,
+    /// The median cluster size, the mean of the middle two for an even
+    /// number of clusters.
+    pub median_cluster_size: Option<f64>,
+    /// The largest size such that clusters at least that large hold half of
+    /// all sequences.
+    pub n50: Option<usize>,
+}
+
+/// Reads every cluster from `clusters` and summarizes them. Only the number
+/// of clusters of each size is kept, so memory grows with the number of
+/// distinct sizes rather than with the file. Fails at the first error.
+pub fn summarize<I: IntoIterator<Item = Result<Cluster>>>(clusters: I) -> Result<ClstrSummary> {
+    let mut summary = ClstrSummary {
+        cluster_count: 0,
+        sequence_count: 0,
+        singleton_count: 0,
+        total_length: 0,
+        min_cluster_size: None,
+        max_cluster_size: None,
+        largest_cluster_id: None,
+        mean_cluster_size: None,
+        median_cluster_size: None,
+        n50: None,
+    };
+    // the number of clusters of each size
+    let mut sizes: BTreeMap<usize, usize> = BTreeMap::new();
+
+    for cluster in clusters {
+        let cluster = cluster?;
+        let size = cluster.size();
+        summary.cluster_count += 1;
+        summary.sequence_count += size;
+        if cluster.is_singleton() {
+            summary.singleton_count += 1;
+        }
+        summary.total_length += cluster.total_sequence_length();
+        // the first of the largest clusters is kept
+        if summary.max_cluster_size.is_none_or(|max| size > max) {
+            summary.max_cluster_size = Some(size);
+            summary.largest_cluster_id = Some(cluster.cluster_id());
+        }
+        *sizes.entry(size).or_insert(0) += 1;
+    }
+
+    if summary.cluster_count == 0 {
+        return Ok(summary);
+    }
+    summary.min_cluster_size = sizes.keys().next().copied();
+    summary.mean_cluster_size = Some(summary.sequence_count as f64 / summary.cluster_count as f64);
+    summary.median_cluster_size = Some(median(&sizes, summary.cluster_count));
+
+    let mut covered = 0;
+    for (&size, &count) in sizes.iter().rev() {
+        covered += size * count;
+        if 2 * covered >= summary.sequence_count {
+            summary.n50 = Some(size);
+            break;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// The number of clusters of each size in a file, returned by
+/// [`size_distribution`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeDistribution {
+    /// The number of clusters of each size, for the sizes that occur.
+    counts: BTreeMap<usize, u64>,
+}
+
+impl SizeDistribution {
+    /// Returns the number of clusters of each size that occurs, smallest
+    /// first.
+    pub fn counts(&self) -> &BTreeMap<usize, u64> {
+        &self.counts
+    }
+
+    /// Returns the number of clusters of each size, consuming the
+    /// distribution.
+    pub fn into_counts(self) -> BTreeMap<usize, u64> {
+        self.counts
+    }
+
+    /// Returns the number of clusters.
+    pub fn cluster_count(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Returns, for each size `k` that occurs, the number of clusters of
+    /// size `k` or more.
+    pub fn cumulative(&self) -> BTreeMap<usize, u64> {
+        let mut at_least = 0;
+        self.counts
+            .iter()
+            .rev()
+            .map(|(&size, &count)| {
+                at_least += count;
+                (size, at_least)
+            })
+            .collect()
+    }
+
+    /// Returns the size of the cluster at the 1-based `rank` when clusters
+    /// are ordered smallest first, or `None` if there are fewer clusters.
+    pub fn nth_smallest(&self, rank: u64) -> Option<usize> {
+        let mut seen = 0;
+        self.counts.iter().find_map(|(&size, &count)| {
+            seen += count;
+            (rank >= 1 && rank <= seen).then_some(size)
+        })
+    }
+}
+
+/// Counts the clusters of each size in `clusters`, keeping only the counts.
+/// Fails at the first error.
+pub fn size_distribution<I: IntoIterator<Item = Result<Cluster>>>(
+    clusters: I,
+) -> Result<SizeDistribution> {
+    let mut counts = BTreeMap::new();
+    for cluster in clusters {
+        *counts.entry(cluster?.size()).or_insert(0) += 1;
+    }
+    Ok(SizeDistribution { counts })
+}
+
+/// The identities of the member sequences of a file, binned by
+/// [`identity_histogram`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdentityHistogram {
+    /// The lower bound of each bin and the number of identities in it, in
+    /// order from 0%. Every bin but the last is half-open; the last also
+    /// holds 100%.
+    pub bins: Vec<(f32, u64)>,
+    /// The number of members skipped for having no identity.
+    pub skipped: u64,
+}
+
+/// Bins the identities of every member sequence in `clusters` into bins
+/// `bin_width` wide, from 0 to 100%. Representatives are left out. If the
+/// width does not divide 100, the last bin is narrower than the rest.
+/// Identities outside 0 to 100% are counted in the nearest bin. Fails at the
+/// first error.
+///
+/// # Panics
+///
+/// Panics if `bin_width` is not a positive number.
+pub fn identity_histogram<I: IntoIterator<Item = Result<Cluster>>>(
+    clusters: I,
+    bin_width: f32,
+) -> Result<IdentityHistogram> {
+    assert!(bin_width > 0.0, "bin width {bin_width} is not positive");
+
+    // in f64 from its shortest decimal form, so that a width such as 0.1
+    // gives 1000 bins rather than 1001 and labels such as 11.9 rather than
+    // 11.900001
+    let width: f64 = bin_width.to_string().parse().unwrap();
+    let n_bins = ((100.0 / width) - 1e-9).ceil().max(1.0) as usize;
+    let mut counts = vec![0; n_bins];
+    let mut skipped = 0;
+
+    for cluster in clusters {
+        for seq in cluster?.iter().filter(|s| !s.is_representative()) {
+            match seq.identity() {
+                Some(identity) => {
+                    // an f32 identity is slightly off its decimal value, so a
+                    // quotient within a millionth below a whole number is
+                    // taken as that number
+                    let bin = (f64::from(identity) / width * (1.0 + 1e-6))
+                        .floor()
+                        .max(0.0) as usize;
+                    counts[bin.min(n_bins - 1)] += 1;
+                }
+                None => skipped += 1,
+            }
+        }
+    }
+
+    Ok(IdentityHistogram {
+        bins: counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| ((i as f64 * width) as f32, count))
+            .collect(),
+        skipped,
+    })
+}
+
+/// Returns the median of `count` sizes, given as the number of each.
+fn median(sizes: &BTreeMap<usize, usize>, count: usize) -> f64 {
+    // the 0-based ranks of the middle one or two sizes
+    let (low, high) = ((count - 1) / 2, count / 2);
+    let nth = |rank: usize| {
+        let mut seen = 0;
+        for (&size, &n) in sizes {
+            seen += n;
+            if rank < seen {
+                return size;
+            }
+        }
+        unreachable!("rank {rank} is below the count of {count}")
+    };
+    (nth(low) + nth(high)) as f64 / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let data = ">Cluster 0
+0    100aa, >a... *
+>Cluster 4
+0    100aa, >b... *
+1    200aa, >c... at 90.00%
+2    300aa, >d... at 90.00%
+>Cluster 2
+0    100aa, >e... *
+1    100aa, >f... at 90.00%
+2    100aa, >g... at 90.00%
+>Cluster 3
+0    50aa, >h... *
+";
+        let summary = summarize(crate::from_str(data)).unwrap();
+        assert_eq!(summary.cluster_count, 4);
+        assert_eq!(summary.sequence_count, 8);
+        assert_eq!(summary.singleton_count, 2);
+        assert_eq!(summary.total_length, 1050);
+        assert_eq!(summary.min_cluster_size, Some(1));
+        assert_eq!(summary.max_cluster_size, Some(3));
+        assert_eq!(summary.largest_cluster_id, Some(4));
+        assert_eq!(summary.mean_cluster_size, Some(2.0));
+        assert_eq!(summary.median_cluster_size, Some(2.0));
+        // the two clusters of 3 hold 6 of the 8 sequences
+        assert_eq!(summary.n50, Some(3));
+
+        let odd = summarize(crate::from_str(data).take(3)).unwrap();
+        assert_eq!(odd.median_cluster_size, Some(3.0));
+
+        let empty = summarize(crate::from_str("")).unwrap();
+        assert_eq!(empty.cluster_count, 0);
+        assert_eq!(empty.mean_cluster_size, None);
+        assert_eq!(empty.n50, None);
+
+        assert!(summarize(crate::from_str(">Cluster x\n")).is_err());
+    }
+
+    #[test]
+    fn test_size_distribution() {
+        let data = ">Cluster 0
+0    100aa, >a... *
+>Cluster 1
+0    100aa, >b... *
+1    100aa, >c... at 90.00%
+2    100aa, >d... at 90.00%
+>Cluster 2
+0    100aa, >e... *
+>Cluster 3
+0    100aa, >f... *
+1    100aa, >g... at 90.00%
+";
+        let distribution = size_distribution(crate::from_str(data)).unwrap();
+        assert_eq!(
+            distribution.counts(),
+            &BTreeMap::from([(1, 2), (2, 1), (3, 1)])
+        );
+        assert_eq!(
+            distribution.cumulative(),
+            BTreeMap::from([(1, 4), (2, 2), (3, 1)])
+        );
+        assert_eq!(distribution.cluster_count(), 4);
+        assert_eq!(distribution.nth_smallest(1), Some(1));
+        assert_eq!(distribution.nth_smallest(3), Some(2));
+        assert_eq!(distribution.nth_smallest(4), Some(3));
+        assert_eq!(distribution.nth_smallest(0), None);
+        assert_eq!(distribution.nth_smallest(5), None);
+
+        let empty = size_distribution(crate::from_str("")).unwrap();
+        assert!(empty.cumulative().is_empty());
+        assert!(size_distribution(crate::from_str(">Cluster x\n")).is_err());
+    }
+
+    #[test]
+    fn test_identity_histogram() {
+        let data = ">Cluster 0
+0    100aa, >a... *
+1    100aa, >b... at 100.00%
+2    100aa, >c... at 99.50%
+3    100aa, >d... at 95.00%
+4    100aa, >e...
+>Cluster 1
+0    100aa, >f... at 12.00%
+1    100aa, >g... *
+";
+        let histogram = identity_histogram(crate::from_str(data), 5.0).unwrap();
+        assert_eq!(histogram.bins.len(), 20);
+        assert_eq!(histogram.bins[0], (0.0, 0));
+        assert_eq!(histogram.bins[2], (10.0, 1));
+        assert_eq!(histogram.bins[19], (95.0, 3));
+        assert_eq!(histogram.bins.iter().map(|b| b.1).sum::<u64>(), 4);
+        assert_eq!(histogram.skipped, 1);
+
+        // 30 does not divide 100, leaving a last bin from 90%
+        let histogram = identity_histogram(crate::from_str(data), 30.0).unwrap();
+        assert_eq!(
+            histogram.bins,
+            vec![(0.0, 1), (30.0, 0), (60.0, 0), (90.0, 3)]
+        );
+        assert_eq!(
+            identity_histogram(crate::from_str(data), 0.1)
+                .unwrap()
+                .bins
+                .len(),
+            1000
+        );
+        assert!(identity_histogram(crate::from_str(">Cluster x\n"), 1.0).is_err());
+
+        // identities on a boundary land in the bin they start, whatever the
+        // rounding of the width and the identity
+        let data = ">Cluster 0
+0    100aa, >a... *
+1    100aa, >b... at 12.00%
+2    100aa, >c... at 12.20%
+3    100aa, >d... at 12.29%
+4    100aa, >e... at 99.90%
+";
+        let histogram = identity_histogram(crate::from_str(data), 0.1).unwrap();
+        let filled: Vec<(f32, u64)> = histogram.bins.iter().copied().filter(|b| b.1 > 0).collect();
+        assert_eq!(filled, vec![(12.0, 1), (12.2, 2), (99.9, 1)]);
+        assert_eq!(histogram.bins[119].0, 11.9);
+    }
+}
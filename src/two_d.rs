@@ -0,0 +1,250 @@
+/*!
+Parsing of the `.clstr` files written by CD-HIT-2D, which clusters the
+sequences of a second database against the sequences of a first.
+*/
+
+use std::io::BufRead;
+
+use crate::{LengthUnit, LineState, ParseOptions, Record, Result, Sequence};
+
+/// A single sequence entry in a CD-HIT-2D cluster.
+#[derive(Debug)]
+pub struct TwoDSequence {
+    /// The length of the sequence.
+    length: u32,
+    /// Whether amino acid or nucleotide
+    unit: LengthUnit,
+    /// The sequence ID.
+    id: String,
+    /// The percentage identity to the first-database representative.
+    identity: Option<f32>,
+    /// The second identity of an `at 99.89/100%` style entry.
+    secondary_identity: Option<f32>,
+    /// The input database the sequence came from, 1 or 2.
+    database: u8,
+}
+
+impl TwoDSequence {
+    /// Build from a sequence parsed off `line`, which holds the secondary
+    /// identity the plain [`Sequence`] drops.
+    fn from_sequence(seq: Sequence, line: &str) -> Self {
+        TwoDSequence {
+            length: seq.length,
+            unit: seq.unit,
+            id: seq.id,
+            identity: seq.identity,
+            secondary_identity: parse_secondary_identity(line),
+            // each cluster is led by a first-database sequence, marked as the
+            // representative, and the rest are its hits in the second
+            database: if seq.is_representative { 1 } else { 2 },
+        }
+    }
+
+    /// Returns the length of the sequence.
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// Returns whether the length is in amino acids or nucleotides.
+    pub fn unit(&self) -> LengthUnit {
+        self.unit
+    }
+
+    /// Returns the sequence ID.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the percentage identity to the first-database representative,
+    /// if available.
+    pub fn identity(&self) -> Option<f32> {
+        self.identity
+    }
+
+    /// Returns the second identity of an `at 99.89/100%` style entry, if
+    /// available.
+    pub fn secondary_identity(&self) -> Option<f32> {
+        self.secondary_identity
+    }
+
+    /// Returns the input database the sequence came from, 1 or 2.
+    pub fn database(&self) -> u8 {
+        self.database
+    }
+}
+
+/// A CD-HIT-2D cluster: a first-database representative and its hits.
+#[derive(Debug)]
+pub struct TwoDCluster {
+    /// The cluster ID.
+    cluster_id: usize,
+    /// The sequences in this cluster.
+    sequences: Vec<TwoDSequence>,
+}
+
+impl TwoDCluster {
+    /// Returns the cluster ID.
+    pub fn cluster_id(&self) -> usize {
+        self.cluster_id
+    }
+
+    /// Returns the sequences in this cluster.
+    pub fn sequences(&self) -> &[TwoDSequence] {
+        &self.sequences
+    }
+
+    /// Returns the first-database representative, if available.
+    pub fn get_representative(&self) -> Option<&TwoDSequence> {
+        self.sequences.iter().find(|s| s.database == 1)
+    }
+
+    /// Returns the number of sequences in this cluster.
+    pub fn size(&self) -> usize {
+        self.sequences.len()
+    }
+}
+
+/// Returns the number after the `/` of an identity such as `99.89/100%`.
+/// The CD-HIT-EST `+/` and `-/` strand prefixes have no second identity.
+fn parse_secondary_identity(line: &str) -> Option<f32> {
+    // skip past the ID, which may contain " at " itself
+    let after_id = &line[line.find("...")? + 3..];
+    let rest = &after_id[after_id.find(" at ")? + 4..];
+    let token = rest.split_ascii_whitespace().next()?.trim_end_matches('%');
+    let (a, b) = token.split_once('/')?;
+
+    match a.trim() {
+        "" | "+" | "-" => None,
+        _ => b.trim().parse().ok(),
+    }
+}
+
+/// Iterator to parse the `.clstr` output of CD-HIT-2D, line by line as
+/// [`crate::ClstrParser`] does.
+pub struct ClstrParser2D<R: BufRead> {
+    /// The reader to parse the file.
+    reader: R,
+    /// The current cluster being parsed.
+    current_cluster: Option<TwoDCluster>,
+    /// Buffer holding the line currently being parsed.
+    buf: Vec<u8>,
+    /// The state of parsing.
+    state: LineState,
+}
+
+impl<R: BufRead> ClstrParser2D<R> {
+    /// Creates a parser with the default [`ParseOptions`].
+    pub fn new(reader: R) -> Self {
+        ClstrParser2D::with_options(reader, ParseOptions::default())
+    }
+
+    /// Creates a parser with the given options.
+    pub fn with_options(reader: R, options: ParseOptions) -> Self {
+        ClstrParser2D {
+            reader,
+            current_cluster: None,
+            buf: Vec::new(),
+            state: LineState::new(options),
+        }
+    }
+
+    /// Returns the 1-based number of the last line read, or 0 if nothing has
+    /// been read yet.
+    pub fn line_number(&self) -> u64 {
+        self.state.line
+    }
+}
+
+impl<R: BufRead> Iterator for ClstrParser2D<R> {
+    type Item = Result<TwoDCluster>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_until(b'\n', &mut self.buf) {
+                Ok(0) => return self.current_cluster.take().map(Ok),
+                Ok(_) => {}
+                Err(e) => return Some(Err(self.state.read_error(e))),
+            }
+
+            match self.state.parse_line(&self.buf) {
+                Some(Ok(Record::Header(cluster_id, _))) => {
+                    let next_cluster = Some(TwoDCluster {
+                        cluster_id,
+                        sequences: Vec::new(),
+                    });
+                    if let Some(c) = std::mem::replace(&mut self.current_cluster, next_cluster) {
+                        return Some(Ok(c));
+                    }
+                }
+                Some(Ok(Record::Sequence(_, _, seq))) => {
+                    let line = String::from_utf8_lossy(&self.buf);
+                    if let Some(c) = &mut self.current_cluster {
+                        c.sequences.push(TwoDSequence::from_sequence(seq, &line));
+                    }
+                }
+                Some(Ok(Record::Truncated)) => self.current_cluster = None,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_2d() {
+        let data = b">Cluster 0
+0       2799aa, >db1_a... *
+1       2214aa, >db2_x... at 99.00%
+2       2190aa, >db2_y... at 97.10/98.40%
+>Cluster 1
+0       812aa, >db1_b... *
+" as &[u8];
+
+        let clusters: Vec<TwoDCluster> = ClstrParser2D::new(data).collect::<Result<_>>().unwrap();
+        assert_eq!(clusters.len(), 2);
+
+        let first = &clusters[0];
+        assert_eq!(first.size(), 3);
+        assert_eq!(first.get_representative().unwrap().id(), "db1_a");
+        assert_eq!(first.sequences()[0].database(), 1);
+        assert_eq!(first.sequences()[1].database(), 2);
+        assert_eq!(first.sequences()[1].identity(), Some(99.0));
+        assert_eq!(first.sequences()[1].secondary_identity(), None);
+        assert_eq!(first.sequences()[2].identity(), Some(97.1));
+        assert_eq!(first.sequences()[2].secondary_identity(), Some(98.4));
+
+        assert_eq!(clusters[1].cluster_id(), 1);
+        assert_eq!(clusters[1].sequences()[0].length(), 812);
+
+        // CRLF input parses the same, secondary identities included
+        let crlf = String::from_utf8(data.to_vec())
+            .unwrap()
+            .replace('\n', "\r\n");
+        let crlf: Vec<TwoDCluster> = ClstrParser2D::new(crlf.as_bytes())
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(crlf[0].sequences()[2].secondary_identity(), Some(98.4));
+        assert_eq!(crlf[0].get_representative().unwrap().id(), "db1_a");
+
+        // a cut header drops only itself
+        let options = ParseOptions {
+            on_truncation: crate::TruncationPolicy::Drop,
+            ..Default::default()
+        };
+        let cut = b">Cluster 0\n0       812aa, >db1_b... *\n>Cluster 1" as &[u8];
+        let clusters: Vec<TwoDCluster> = ClstrParser2D::with_options(cut, options)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(clusters.len(), 1);
+
+        assert!(ClstrParser2D::new(b">Cluster 0\n0 x\n" as &[u8])
+            .next()
+            .unwrap()
+            .is_err());
+    }
+}
@@ -0,0 +1,480 @@
+/*!
+Structural checks over whole `.clstr` files.
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::BufRead;
+
+use crate::{ClstrParser, Record, Result, Sequence};
+
+/// A structural problem in a `.clstr` file that does not stop it being parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationWarning {
+    /// A cluster has no representative sequence.
+    NoRepresentative { cluster_id: usize },
+    /// A cluster has more than one representative sequence.
+    MultipleRepresentatives { cluster_id: usize, count: usize },
+    /// The index in the first column of a sequence line does not match the
+    /// position of the sequence in its cluster.
+    IndexOutOfOrder {
+        cluster_id: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A sequence ID occurs more than once in a cluster.
+    DuplicateSequenceId { cluster_id: usize, id: String },
+    /// A sequence ID occurs in more than one cluster.
+    DuplicateAcrossClusters {
+        id: String,
+        first_cluster_id: usize,
+        cluster_id: usize,
+    },
+    /// The representative sequence has an identity to itself.
+    RepresentativeHasIdentity { cluster_id: usize, id: String },
+    /// A sequence has an identity outside 0 to 100%. Identities outside the
+    /// parser's [`crate::ParseOptions::min_identity_value`] and
+    /// [`crate::ParseOptions::max_identity_value`] fail parsing instead.
+    IdentityOutOfRange {
+        cluster_id: usize,
+        id: String,
+        identity: f32,
+    },
+    /// The last line has no newline, so the last cluster may be missing
+    /// sequences. The cluster ID is `None` if the cluster was dropped or the
+    /// line came before any header.
+    Truncated { cluster_id: Option<usize> },
+}
+
+impl ValidationWarning {
+    /// Returns whether this problem makes the file unreliable to use, as
+    /// opposed to a quirk of formatting that CD-HIT output can have, like an
+    /// index out of order or an identity on the representative.
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self,
+            ValidationWarning::IndexOutOfOrder { .. }
+                | ValidationWarning::RepresentativeHasIdentity { .. }
+        )
+    }
+}
+
+impl fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationWarning::NoRepresentative { cluster_id } => {
+                write!(f, "cluster {cluster_id} has no representative")
+            }
+            ValidationWarning::MultipleRepresentatives { cluster_id, count } => {
+                write!(f, "cluster {cluster_id} has {count} representatives")
+            }
+            ValidationWarning::IndexOutOfOrder {
+                cluster_id,
+                expected,
+                found,
+            } => write!(
+                f,
+                "cluster {cluster_id} has sequence index {found} where {expected} was expected"
+            ),
+            ValidationWarning::DuplicateSequenceId { cluster_id, id } => {
+                write!(
+                    f,
+                    "cluster {cluster_id} contains sequence {id} more than once"
+                )
+            }
+            ValidationWarning::DuplicateAcrossClusters {
+                id,
+                first_cluster_id,
+                cluster_id,
+            } => write!(
+                f,
+                "sequence {id} is in cluster {first_cluster_id} and again in cluster {cluster_id}"
+            ),
+            ValidationWarning::RepresentativeHasIdentity { cluster_id, id } => write!(
+                f,
+                "cluster {cluster_id} has representative {id} with an identity"
+            ),
+            ValidationWarning::IdentityOutOfRange {
+                cluster_id,
+                id,
+                identity,
+            } => write!(
+                f,
+                "cluster {cluster_id} has sequence {id} with identity {identity}%, outside 0 to 100%"
+            ),
+            ValidationWarning::Truncated {
+                cluster_id: Some(cluster_id),
+            } => write!(
+                f,
+                "file is truncated, cluster {cluster_id} may be missing sequences"
+            ),
+            ValidationWarning::Truncated { cluster_id: None } => {
+                write!(f, "file is truncated, the last line has no newline")
+            }
+        }
+    }
+}
+
+/// Where a sequence ID has been seen so far.
+enum Seen {
+    /// Once, in the given cluster.
+    Once(usize),
+    /// More than once, recorded at the given position of the duplicates.
+    Duplicate(usize),
+}
+
+/// The sequence IDs of a whole file, keeping the clusters of only those
+/// that occur more than once.
+#[derive(Default)]
+struct DuplicateIds {
+    seen: HashMap<String, Seen>,
+    duplicates: Vec<(String, Vec<usize>)>,
+}
+
+impl DuplicateIds {
+    /// Records `id` in `cluster_id`, returning the cluster it was first seen
+    /// in if it was seen before.
+    fn record(&mut self, id: &str, cluster_id: usize) -> Option<usize> {
+        let Some(seen) = self.seen.get_mut(id) else {
+            self.seen.insert(id.to_string(), Seen::Once(cluster_id));
+            return None;
+        };
+
+        match *seen {
+            Seen::Once(first) => {
+                *seen = Seen::Duplicate(self.duplicates.len());
+                self.duplicates
+                    .push((id.to_string(), vec![first, cluster_id]));
+                Some(first)
+            }
+            Seen::Duplicate(position) => {
+                let clusters = &mut self.duplicates[position].1;
+                clusters.push(cluster_id);
+                Some(clusters[0])
+            }
+        }
+    }
+}
+
+/// Reads every sequence ID from `parser`, returning each ID that occurs more
+/// than once with the IDs of the clusters it occurs in, one per occurrence.
+/// IDs are returned in the order their second occurrence was read. Only the
+/// IDs are kept in memory, not the clusters.
+pub fn find_duplicate_ids<R: BufRead>(
+    mut parser: ClstrParser<R>,
+) -> Result<Vec<(String, Vec<usize>)>> {
+    let mut ids = DuplicateIds::default();
+    let mut cluster_id = None;
+
+    while let Some(record) = parser.next_record() {
+        match record? {
+            Record::Header(id, _) => cluster_id = Some(id),
+            Record::Sequence(_, _, seq) => {
+                if let Some(cluster_id) = cluster_id {
+                    ids.record(&seq.id, cluster_id);
+                }
+            }
+            Record::Truncated => cluster_id = None,
+        }
+    }
+
+    Ok(ids.duplicates)
+}
+
+/// The running checks over the cluster currently being read.
+struct ClusterCheck {
+    cluster_id: usize,
+    /// The number of sequences seen so far.
+    position: usize,
+    /// The number of representatives seen so far.
+    representatives: usize,
+    /// The sequence IDs seen so far.
+    ids: HashSet<String>,
+}
+
+impl ClusterCheck {
+    fn new(cluster_id: usize) -> Self {
+        ClusterCheck {
+            cluster_id,
+            position: 0,
+            representatives: 0,
+            ids: HashSet::new(),
+        }
+    }
+
+    fn check(
+        &mut self,
+        index: usize,
+        seq: Sequence,
+        file_ids: &mut DuplicateIds,
+        warnings: &mut Vec<ValidationWarning>,
+    ) {
+        let cluster_id = self.cluster_id;
+
+        if index != self.position {
+            warnings.push(ValidationWarning::IndexOutOfOrder {
+                cluster_id,
+                expected: self.position,
+                found: index,
+            });
+        }
+        self.position += 1;
+
+        if let Some(identity) = seq.identity.filter(|i| !(0.0..=100.0).contains(i)) {
+            warnings.push(ValidationWarning::IdentityOutOfRange {
+                cluster_id,
+                id: seq.id.clone(),
+                identity,
+            });
+        }
+
+        if seq.is_representative {
+            self.representatives += 1;
+            if seq.identity.is_some() {
+                warnings.push(ValidationWarning::RepresentativeHasIdentity {
+                    cluster_id,
+                    id: seq.id.clone(),
+                });
+            }
+        }
+
+        let first_cluster_id = file_ids.record(&seq.id, cluster_id);
+        if self.ids.contains(&seq.id) {
+            warnings.push(ValidationWarning::DuplicateSequenceId {
+                cluster_id,
+                id: seq.id,
+            });
+        } else {
+            if let Some(first_cluster_id) = first_cluster_id {
+                warnings.push(ValidationWarning::DuplicateAcrossClusters {
+                    id: seq.id.clone(),
+                    first_cluster_id,
+                    cluster_id,
+                });
+            }
+            self.ids.insert(seq.id);
+        }
+    }
+
+    fn finish(self, warnings: &mut Vec<ValidationWarning>) {
+        let cluster_id = self.cluster_id;
+        match self.representatives {
+            0 => warnings.push(ValidationWarning::NoRepresentative { cluster_id }),
+            1 => {}
+            count => {
+                warnings.push(ValidationWarning::MultipleRepresentatives { cluster_id, count })
+            }
+        }
+    }
+}
+
+impl<R: BufRead> ClstrParser<R> {
+    /// Consumes the parser, checking every cluster for structural problems.
+    /// Problems are returned as warnings, in the order they are found; I/O and
+    /// parse errors still fail.
+    pub fn validate(mut self) -> Result<Vec<ValidationWarning>> {
+        let mut warnings = Vec::new();
+        let mut current: Option<ClusterCheck> = None;
+        let mut file_ids = DuplicateIds::default();
+
+        while let Some(record) = self.next_record() {
+            match record? {
+                Record::Header(cluster_id, _) => {
+                    if let Some(check) = current.replace(ClusterCheck::new(cluster_id)) {
+                        check.finish(&mut warnings);
+                    }
+                }
+                Record::Sequence(_, index, seq) => {
+                    if let Some(ref mut check) = current {
+                        check.check(index, seq, &mut file_ids, &mut warnings);
+                    }
+                }
+                Record::Truncated => current = None,
+            }
+        }
+
+        let last_cluster_id = current.as_ref().map(|check| check.cluster_id);
+        if let Some(check) = current {
+            check.finish(&mut warnings);
+        }
+        if self.state.truncated {
+            warnings.push(ValidationWarning::Truncated {
+                cluster_id: last_cluster_id,
+            });
+        }
+
+        Ok(warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let data = ">Cluster 0
+0    4481aa, >sp|P0C6T5|R1A_BCHK5... at 99.89%
+1    7182aa, >sp|P0C6W4|R1AB_BCHK5... *
+>Cluster 1
+0    4471aa, >sp|P0C6U3|R1A_CVHN1... at 99.91%
+>Cluster 2
+0    4441aa, >sp|P0C6U4|R1A_CVHN2... *
+2    4421aa, >sp|P0C6U5|R1A_CVHN5... at 100.00% *
+3    4421aa, >sp|P0C6U5|R1A_CVHN5... at 81.52%
+";
+
+        let warnings = crate::from_str(data).validate().unwrap();
+        assert_eq!(
+            warnings,
+            vec![
+                ValidationWarning::NoRepresentative { cluster_id: 1 },
+                ValidationWarning::IndexOutOfOrder {
+                    cluster_id: 2,
+                    expected: 1,
+                    found: 2
+                },
+                ValidationWarning::RepresentativeHasIdentity {
+                    cluster_id: 2,
+                    id: "sp|P0C6U5|R1A_CVHN5".to_string()
+                },
+                ValidationWarning::IndexOutOfOrder {
+                    cluster_id: 2,
+                    expected: 2,
+                    found: 3
+                },
+                ValidationWarning::DuplicateSequenceId {
+                    cluster_id: 2,
+                    id: "sp|P0C6U5|R1A_CVHN5".to_string()
+                },
+                ValidationWarning::MultipleRepresentatives {
+                    cluster_id: 2,
+                    count: 2
+                },
+            ]
+        );
+        assert_eq!(warnings[0].to_string(), "cluster 1 has no representative");
+
+        let clean = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_nt.clstr");
+        assert!(crate::from_path(clean)
+            .unwrap()
+            .validate()
+            .unwrap()
+            .is_empty());
+
+        assert!(crate::from_str(">Cluster 0\n0    44bp, >x... *\n")
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_identities_and_truncation() {
+        let data = ">Cluster 0
+0    4481aa, >a... *
+1    4471aa, >b... at -3.50%
+2    4461aa, >c... at 120.00%
+>Cluster 1
+0    4441aa, >d... *";
+        let options = crate::ParseOptions {
+            max_identity_value: f32::INFINITY,
+            min_identity_value: f32::NEG_INFINITY,
+            ..Default::default()
+        };
+        let warnings = ClstrParser::with_options(data.as_bytes(), options)
+            .validate()
+            .unwrap();
+        assert_eq!(
+            warnings,
+            vec![
+                ValidationWarning::IdentityOutOfRange {
+                    cluster_id: 0,
+                    id: "b".to_string(),
+                    identity: -3.5
+                },
+                ValidationWarning::IdentityOutOfRange {
+                    cluster_id: 0,
+                    id: "c".to_string(),
+                    identity: 120.0
+                },
+                ValidationWarning::Truncated {
+                    cluster_id: Some(1)
+                },
+            ]
+        );
+        assert!(warnings.iter().all(ValidationWarning::is_error));
+        assert_eq!(
+            warnings[2].to_string(),
+            "file is truncated, cluster 1 may be missing sequences"
+        );
+
+        // outside the default bounds, the identities fail parsing
+        assert!(crate::from_str(data).validate().is_err());
+        let options = crate::ParseOptions {
+            max_identity_value: f32::INFINITY,
+            ..Default::default()
+        };
+        assert!(ClstrParser::with_options(data.as_bytes(), options)
+            .validate()
+            .is_err());
+        assert!(!ValidationWarning::IndexOutOfOrder {
+            cluster_id: 0,
+            expected: 0,
+            found: 1
+        }
+        .is_error());
+    }
+
+    #[test]
+    fn test_find_duplicate_ids() {
+        let data = ">Cluster 0
+0    4481aa, >a... *
+1    4471aa, >b... at 99.91%
+>Cluster 1
+0    4441aa, >b... *
+1    4421aa, >c... at 81.52%
+>Cluster 2
+0    4441aa, >a... *
+1    4441aa, >a... at 100.00%
+2    4441aa, >b... at 99.00%
+";
+        assert_eq!(
+            find_duplicate_ids(crate::from_str(data)).unwrap(),
+            vec![
+                ("b".to_string(), vec![0, 1, 2]),
+                ("a".to_string(), vec![0, 2, 2]),
+            ]
+        );
+
+        let warnings = crate::from_str(data).validate().unwrap();
+        assert_eq!(
+            warnings[0],
+            ValidationWarning::DuplicateAcrossClusters {
+                id: "b".to_string(),
+                first_cluster_id: 0,
+                cluster_id: 1
+            }
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "sequence b is in cluster 0 and again in cluster 1"
+        );
+        // a repeat within the cluster is reported as such, not again
+        assert_eq!(warnings.len(), 4);
+        assert!(matches!(
+            warnings[2],
+            ValidationWarning::DuplicateSequenceId { cluster_id: 2, .. }
+        ));
+
+        let mut cluster = crate::from_str(data).nth(2).unwrap().unwrap();
+        let removed = cluster.dedup_by_id();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].identity(), Some(100.0));
+        assert_eq!(cluster.size(), 2);
+        assert!(cluster.get_representative().is_some());
+
+        let clean = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test_nt.clstr");
+        assert!(find_duplicate_ids(crate::from_path(clean).unwrap())
+            .unwrap()
+            .is_empty());
+    }
+}
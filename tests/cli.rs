@@ -0,0 +1,534 @@
+//! Runs the `clstr` binary, checking the output files and exit codes that
+//! the subcommands promise.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use clstr::{Cluster, Result};
+
+/// Returns the path of a fixture in `data/`.
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("data")
+        .join(name)
+}
+
+/// Copies a fixture into `dir`, for the subcommands that write their output
+/// next to the input.
+fn copy_fixture(name: &str, dir: &Path) -> PathBuf {
+    let path = dir.join(name);
+    std::fs::copy(fixture(name), &path).unwrap();
+    path
+}
+
+/// Returns an empty directory for one test, unique to this process so that
+/// concurrent runs do not collide.
+fn scratch_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("clstr_cli_{}_{test}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs the binary with `args`.
+fn clstr(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_clstr"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Parses every cluster of the file at `path`.
+fn read(path: &Path) -> Vec<Cluster> {
+    clstr::from_path(path)
+        .unwrap()
+        .collect::<Result<_>>()
+        .unwrap()
+}
+
+#[test]
+fn test_cat_gzip_output() {
+    let dir = scratch_dir("cat_gzip_output");
+    let output = dir.join("merged.clstr.gz");
+    let input = fixture("test.clstr");
+
+    let run = clstr(&[
+        "cat",
+        input.to_str().unwrap(),
+        input.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    assert!(run.status.success(), "{run:?}");
+
+    // a complete gzip stream, trailer included, that parses back
+    let bytes = std::fs::read(&output).unwrap();
+    assert_eq!(&bytes[..2], [0x1f, 0x8b]);
+    let merged = read(&output);
+    assert_eq!(merged.len(), 4);
+    assert_eq!(merged.iter().map(Cluster::size).sum::<usize>(), 16);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cat_renumbers_repeated_ids() {
+    let dir = scratch_dir("cat_renumbers_repeated_ids");
+    let input = dir.join("repeated.clstr");
+    std::fs::write(
+        &input,
+        ">Cluster 0\n0\t100aa, >a... *\n>Cluster 0\n0\t100aa, >b... *\n",
+    )
+    .unwrap();
+    let output = dir.join("merged.clstr");
+
+    let run = clstr(&[
+        "cat",
+        input.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    assert!(run.status.success(), "{run:?}");
+    let ids: Vec<usize> = read(&output).iter().map(Cluster::cluster_id).collect();
+    assert_eq!(ids, vec![0, 1]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_stats_truncated() {
+    let dir = scratch_dir("stats_truncated");
+    let input = dir.join("truncated.clstr");
+    std::fs::write(
+        &input,
+        ">Cluster 0\n0\t100aa, >a... *\n1\t90aa, >b... at 90.00%",
+    )
+    .unwrap();
+
+    // the totals are printed, then the warning, and the exit is non-zero
+    let run = clstr(&["stats", input.to_str().unwrap()]);
+    assert_eq!(run.status.code(), Some(1));
+    let stdout = String::from_utf8(run.stdout).unwrap();
+    assert_eq!(stdout.lines().nth(1), Some("1\t2\t2"));
+    let stderr = String::from_utf8(run.stderr).unwrap();
+    assert!(stderr.contains("is truncated"), "{stderr}");
+
+    let run = clstr(&["stats", fixture("test.clstr").to_str().unwrap()]);
+    assert!(run.status.success(), "{run:?}");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_validate_exit_codes() {
+    let dir = scratch_dir("validate_exit_codes");
+
+    let run = clstr(&["validate", fixture("test_nt.clstr").to_str().unwrap()]);
+    assert!(run.status.success(), "{run:?}");
+    assert_eq!(run.stdout, b"0 error(s), 0 warning(s)\n");
+
+    // cluster 1 of test.clstr has no representative
+    let run = clstr(&["validate", fixture("test.clstr").to_str().unwrap()]);
+    assert_eq!(run.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(run.stdout).unwrap(),
+        "error: cluster 1 has no representative\n1 error(s), 0 warning(s)\n"
+    );
+
+    // out of order indices are only a warning, unless --strict
+    let input = dir.join("out_of_order.clstr");
+    std::fs::write(
+        &input,
+        ">Cluster 0\n1\t100aa, >a... *\n0\t90aa, >b... at 90.00%\n",
+    )
+    .unwrap();
+    let run = clstr(&["validate", "-q", input.to_str().unwrap()]);
+    assert!(run.status.success(), "{run:?}");
+    assert_eq!(run.stdout, b"PASS\n");
+    let run = clstr(&["validate", "-q", "--strict", input.to_str().unwrap()]);
+    assert_eq!(run.status.code(), Some(1));
+    assert_eq!(run.stdout, b"FAIL\n");
+
+    // identities outside 0 to 100% are reported rather than failing parsing
+    let input = dir.join("out_of_range.clstr");
+    std::fs::write(
+        &input,
+        ">Cluster 0\n0\t100aa, >a... *\n1\t90aa, >b... at -3.50%\n",
+    )
+    .unwrap();
+    let run = clstr(&["validate", input.to_str().unwrap()]);
+    assert_eq!(run.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(run.stdout).unwrap(),
+        "error: cluster 0 has sequence b with identity -3.5%, outside 0 to 100%\n\
+         1 error(s), 0 warning(s)\n"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_rename_strict() {
+    let dir = scratch_dir("rename_strict");
+    let input = dir.join("input.clstr");
+    std::fs::write(
+        &input,
+        ">Cluster 0\n0\t100aa, >a... *\n1\t90aa, >b... at 90.00%\n",
+    )
+    .unwrap();
+    let mapping = dir.join("mapping.tsv");
+    std::fs::write(&mapping, "a\tx\n").unwrap();
+    let output = dir.join("renamed.clstr");
+    let args = [
+        "rename",
+        input.to_str().unwrap(),
+        mapping.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ];
+
+    // b is not in the mapping, so --strict fails before writing anything
+    let run = clstr(&[&args[..], &["--strict"]].concat());
+    assert_eq!(run.status.code(), Some(1));
+    assert!(String::from_utf8(run.stderr)
+        .unwrap()
+        .contains("sequence ID b is not in the mapping"));
+    assert!(!output.exists());
+
+    let run = clstr(&args);
+    assert!(run.status.success(), "{run:?}");
+    let ids: Vec<String> = read(&output)[0]
+        .iter()
+        .map(|s| s.id().to_string())
+        .collect();
+    assert_eq!(ids, vec!["x", "b"]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_filtern_output_names() {
+    let dir = scratch_dir("filtern_output_names");
+    let input = copy_fixture("test_nt.clstr", &dir);
+    let input = input.to_str().unwrap();
+
+    let run = clstr(&["filtern", "-n", "2", "--max", "6", "--complement", input]);
+    assert!(run.status.success(), "{run:?}");
+    let between = read(&dir.join("test_nt.between_2_and_6.clstr"));
+    assert_eq!(between.iter().map(Cluster::size).collect::<Vec<_>>(), [6]);
+    let complement = read(&dir.join("test_nt.not_between_2_and_6.clstr"));
+    assert_eq!(complement.len(), 3);
+
+    let run = clstr(&["filtern", "-n", "2", input]);
+    assert!(run.status.success(), "{run:?}");
+    assert_eq!(read(&dir.join("test_nt.more_than_2.clstr")).len(), 1);
+
+    let run = clstr(&["filtern", "-n", "2", "--max", "1", input]);
+    assert_eq!(run.status.code(), Some(1));
+    assert!(String::from_utf8(run.stderr)
+        .unwrap()
+        .contains("--max 1 is less than --filter-number 2"));
+    assert!(!dir.join("test_nt.between_2_and_1.clstr").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_topn_output_names() {
+    let dir = scratch_dir("topn_output_names");
+    let input = copy_fixture("test_nt.clstr", &dir);
+    let input = input.to_str().unwrap();
+
+    // the cluster of 6 and then one of 1 reach 7 sequences
+    let run = clstr(&["topn", "-n", "7", "--by-sequences", input]);
+    assert!(run.status.success(), "{run:?}");
+    let top = read(&dir.join("test_nt.top7_sequences.clstr"));
+    assert_eq!(top.iter().map(Cluster::size).collect::<Vec<_>>(), [6, 1]);
+    assert_eq!(top[0].cluster_id(), 0);
+
+    let run = clstr(&["topn", "-n", "2", input]);
+    assert!(run.status.success(), "{run:?}");
+    assert_eq!(read(&dir.join("test_nt.top2.clstr")).len(), 2);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_extract() {
+    let dir = scratch_dir("extract");
+    let input = fixture("test_nt.clstr");
+    let output = dir.join("479.clstr");
+
+    let run = clstr(&[
+        "extract",
+        "-c",
+        "479",
+        input.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    assert!(run.status.success(), "{run:?}");
+    let extracted = read(&output);
+    assert_eq!(extracted.len(), 1);
+    assert_eq!(extracted[0].cluster_id(), 479);
+    assert_eq!(extracted[0].size(), 6);
+
+    let run = clstr(&["extract", "-c", "9", input.to_str().unwrap()]);
+    assert_eq!(run.status.code(), Some(1));
+    assert!(String::from_utf8(run.stderr)
+        .unwrap()
+        .contains("no cluster with ID 9"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_compare() {
+    let dir = scratch_dir("compare");
+    // a is listed twice in the first file and counts as in its first cluster
+    let file1 = dir.join("file1.clstr");
+    std::fs::write(
+        &file1,
+        ">Cluster 0\n0\t100aa, >a... *\n1\t90aa, >b... at 90.00%\n\
+         >Cluster 1\n0\t100aa, >c... *\n1\t100aa, >a... at 100.00%\n",
+    )
+    .unwrap();
+    let file2 = dir.join("file2.clstr");
+    std::fs::write(
+        &file2,
+        ">Cluster 0\n0\t100aa, >a... *\n>Cluster 1\n0\t100aa, >b... *\n\
+         1\t100aa, >d... at 95.00%\n",
+    )
+    .unwrap();
+
+    let run = clstr(&["compare", file1.to_str().unwrap(), file2.to_str().unwrap()]);
+    assert!(run.status.success(), "{run:?}");
+    assert_eq!(
+        String::from_utf8(run.stdout).unwrap(),
+        "sequence_id\tcluster_in_file1\tcluster_in_file2\tchanged\n\
+         a\t0\t0\tfalse\n\
+         b\t0\t1\ttrue\n\
+         c\t1\tNA\ttrue\n\
+         d\tNA\t1\ttrue\n"
+    );
+    assert_eq!(
+        String::from_utf8(run.stderr).unwrap(),
+        "1 sequence(s) in the same cluster, 1 moved, 2 in only one file\n"
+    );
+
+    let run = clstr(&[
+        "compare",
+        "--only-changed",
+        file1.to_str().unwrap(),
+        file2.to_str().unwrap(),
+    ]);
+    assert!(run.status.success(), "{run:?}");
+    let stdout = String::from_utf8(run.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 4);
+    assert!(!stdout.contains("\na\t"), "{stdout}");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_split() {
+    let dir = scratch_dir("split");
+    let input = fixture("test_nt.clstr");
+    let input = input.to_str().unwrap();
+    let ids = |part: &str, name: &str| -> Vec<usize> {
+        read(&dir.join(part).join(name))
+            .iter()
+            .map(Cluster::cluster_id)
+            .collect()
+    };
+
+    // clusters 476 to 479 hold 1, 1, 1 and 6 sequences; each goes to the part
+    // its middle falls in, keeping its ID
+    let run = clstr(&[
+        "split",
+        "-n",
+        "3",
+        input,
+        "-o",
+        dir.join("by_clusters").to_str().unwrap(),
+    ]);
+    assert!(run.status.success(), "{run:?}");
+    assert_eq!(ids("by_clusters", "part_000.clstr"), [476]);
+    assert_eq!(ids("by_clusters", "part_001.clstr"), [477, 478]);
+    assert_eq!(ids("by_clusters", "part_002.clstr"), [479]);
+    assert!(!dir.join("by_clusters/part_003.clstr").exists());
+
+    let run = clstr(&[
+        "split",
+        "-n",
+        "2",
+        "--by-sequences",
+        input,
+        "-o",
+        dir.join("by_sequences").to_str().unwrap(),
+    ]);
+    assert!(run.status.success(), "{run:?}");
+    assert_eq!(ids("by_sequences", "part_000.clstr"), [476, 477, 478]);
+    assert_eq!(ids("by_sequences", "part_001.clstr"), [479]);
+
+    // more parts than clusters leaves some of them empty, but still written
+    let run = clstr(&[
+        "split",
+        "-n",
+        "5",
+        input,
+        "-o",
+        dir.join("sparse").to_str().unwrap(),
+    ]);
+    assert!(run.status.success(), "{run:?}");
+    assert_eq!(ids("sparse", "part_000.clstr"), [476]);
+    assert_eq!(ids("sparse", "part_001.clstr"), [477]);
+    assert!(ids("sparse", "part_002.clstr").is_empty());
+    assert_eq!(ids("sparse", "part_003.clstr"), [478]);
+    assert_eq!(ids("sparse", "part_004.clstr"), [479]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_sample() {
+    let dir = scratch_dir("sample");
+    let input = fixture("test_nt.clstr");
+    let input = input.to_str().unwrap();
+    let sample = |args: &[&str], name: &str| -> Vec<Cluster> {
+        let output = dir.join(name);
+        let run = clstr(&[&["sample", input, "-o", output.to_str().unwrap()], args].concat());
+        assert!(run.status.success(), "{run:?}");
+        read(&output)
+    };
+
+    // the same seed draws the same clusters
+    let kept = sample(&["-n", "2", "-s", "7", "--keep-ids"], "kept.clstr");
+    assert_eq!(
+        kept,
+        sample(&["-n", "2", "-s", "7", "--keep-ids"], "again.clstr")
+    );
+    assert_eq!(kept.len(), 2);
+    // in file order, as IDs of the input
+    assert!(kept[0].cluster_id() < kept[1].cluster_id());
+    assert!(kept.iter().all(|c| (476..=479).contains(&c.cluster_id())));
+
+    // the same draw renumbered from 0 without --keep-ids
+    let renumbered = sample(&["-n", "2", "-s", "7"], "renumbered.clstr");
+    assert_eq!(
+        renumbered
+            .iter()
+            .map(Cluster::cluster_id)
+            .collect::<Vec<_>>(),
+        [0, 1]
+    );
+    for (renumbered, kept) in renumbered.iter().zip(&kept) {
+        assert!(renumbered
+            .iter()
+            .map(|s| s.id())
+            .eq(kept.iter().map(|s| s.id())));
+    }
+
+    // asking for more clusters than there are returns them all
+    let all = sample(&["-n", "9", "--keep-ids"], "all.clstr");
+    assert_eq!(
+        all.iter().map(Cluster::cluster_id).collect::<Vec<_>>(),
+        [476, 477, 478, 479]
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_filterid() {
+    let dir = scratch_dir("filterid");
+    let input = fixture("test_nt.clstr");
+    let input = input.to_str().unwrap();
+    let ids = |run: Output| -> Vec<usize> {
+        assert!(run.status.success(), "{run:?}");
+        let output = dir.join("matched.clstr");
+        std::fs::write(&output, run.stdout).unwrap();
+        read(&output).iter().map(Cluster::cluster_id).collect()
+    };
+
+    // ::SUPER_2:8753913-8754035 is a member of 479, not its representative
+    let ids_file = dir.join("ids.txt");
+    std::fs::write(&ids_file, "::SUPER_2:8753913-8754035\n\n").unwrap();
+    let matched = ids(clstr(&[
+        "filterid",
+        "--id",
+        "::SUPER_5:16491930-16492131",
+        "--ids-file",
+        ids_file.to_str().unwrap(),
+        input,
+    ]));
+    assert_eq!(matched, [476, 479]);
+
+    let matched = ids(clstr(&[
+        "filterid",
+        "--ids-file",
+        ids_file.to_str().unwrap(),
+        "--require-representative",
+        input,
+    ]));
+    assert!(matched.is_empty());
+
+    let matched = ids(clstr(&[
+        "filterid",
+        "--id",
+        "::SUPER_2:18124787-18124985",
+        "--require-representative",
+        input,
+    ]));
+    assert_eq!(matched, [479]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_export() {
+    let dir = scratch_dir("export");
+    let input = fixture("test_nt.clstr");
+    let input = input.to_str().unwrap();
+
+    let run = clstr(&["export", input]);
+    assert!(run.status.success(), "{run:?}");
+    let stdout = String::from_utf8(run.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines[..2],
+        [
+            "cluster_id\tsequence_id\tlength\tidentity\tis_representative",
+            "476\t::SUPER_5:16491930-16492131\t201\tNA\ttrue",
+        ]
+    );
+    assert_eq!(lines.len(), 10);
+
+    let output = dir.join("members.csv");
+    let run = clstr(&[
+        "export",
+        input,
+        "-d",
+        ",",
+        "--no-header",
+        "--no-representatives",
+        "--strand",
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    assert!(run.status.success(), "{run:?}");
+    assert!(run.stdout.is_empty());
+    let table = std::fs::read_to_string(&output).unwrap();
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(
+        lines[0],
+        "479,::SUPER_5:20757114-20757236,122,97.54,false,-"
+    );
+    assert_eq!(
+        lines[1],
+        "479,::SUPER_4:13803815-13804001,186,95.70,false,+"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}